@@ -0,0 +1,100 @@
+//! Embeds build provenance into the worker, recorded in the `setup` record
+//!
+//! All values are passed as `BENCH_*` environment variables; missing information (e.g. no git checkout) is left empty.
+
+use std::{path::Path, process::Command};
+
+/// Crates of the storage engines, whose locked versions are recorded
+const ENGINE_CRATES: &[&str] = &[
+    "sled",
+    "fjall",
+    "lsm-tree",
+    "persy",
+    "jammdb",
+    "redb",
+    "nebari",
+    "heed",
+    "lmdb-master-sys",
+    "rocksdb",
+    "librocksdb-sys",
+    "tokio",
+];
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Returns `name=version` pairs of the engine crates in `Cargo.lock`, separated by `;`
+fn locked_versions() -> String {
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let mut versions = vec![];
+
+    for package in lock.split("[[package]]") {
+        let field = |key: &str| {
+            package.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?.trim().strip_prefix('=')?;
+                Some(value.trim().trim_matches('"').to_owned())
+            })
+        };
+
+        if let (Some(name), Some(version)) = (field("name"), field("version")) {
+            if ENGINE_CRATES.contains(&name.as_str()) {
+                versions.push(format!("{name}={version}"));
+            }
+        }
+    }
+
+    versions.join(";")
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    // NOTE: So the dirty flag is refreshed whenever the sources change
+    println!("cargo:rerun-if-changed=src");
+
+    // NOTE: Only watch files that exist, otherwise cargo reruns the script on every build
+    for path in [".git/HEAD", ".git/index"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+
+    let commit = command_output("git", &["rev-parse", "HEAD"]).unwrap_or_default();
+
+    let dirty = command_output("git", &["status", "--porcelain", "--untracked-files=no"])
+        .map(|status| (!status.is_empty()).to_string())
+        .unwrap_or_default();
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let rustc_version = command_output(&rustc, &["--version"]).unwrap_or_default();
+
+    let mut features = std::env::vars()
+        .filter_map(|(key, _)| {
+            let feature = key.strip_prefix("CARGO_FEATURE_")?;
+            Some(feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+
+    println!("cargo:rustc-env=BENCH_GIT_COMMIT={commit}");
+    println!("cargo:rustc-env=BENCH_GIT_DIRTY={dirty}");
+    println!("cargo:rustc-env=BENCH_RUSTC_VERSION={rustc_version}");
+    println!("cargo:rustc-env=BENCH_FEATURES={}", features.join(","));
+    println!("cargo:rustc-env=BENCH_CRATE_VERSIONS={}", locked_versions());
+    println!(
+        "cargo:rustc-env=BENCH_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_default()
+    );
+    println!(
+        "cargo:rustc-env=BENCH_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+}
@@ -32,6 +32,35 @@ export function parseJsonl<T>(text: string): T[] {
     .map((line) => JSON.parse(line));
 }
 
+// Newest result format this page can read (see `schema/result.schema.json`)
+const SCHEMA_VERSION = 1;
+
+type ResultRecord = { type: string; schema_version?: number };
+
+// Returns the system and setup records, followed by all metrics records
+//
+// Other records (end-of-run summaries) are skipped.
+export function parseResults<T>(text: string): T[] {
+  const records = parseJsonl<ResultRecord>(text);
+
+  for (const record of records) {
+    if ((record.schema_version ?? 0) > SCHEMA_VERSION) {
+      throw new Error(`unsupported result schema version ${record.schema_version}`);
+    }
+  }
+
+  const system = records.find((x) => x.type === "system");
+  const setup = records.find((x) => x.type === "setup");
+
+  if (!system || !setup) {
+    throw new Error("result file is missing its system or setup record");
+  }
+
+  const metrics = records.filter((x) => x.type === "metrics");
+
+  return [system, setup, ...metrics] as T[];
+}
+
 type HistoryEntry = MetricEntry;
 
 type MetricEntry = {
@@ -41,7 +70,7 @@ type MetricEntry = {
   du_mib: number;
   disk_mib_w: number;
   disk_mib_r: number;
-  space_amp: number;
+  space_amp: number | null;
   write_amp?: number;
   dataset_size?: number;
   write_ops: number
@@ -517,7 +546,7 @@ function App() {
 
     await readFile(file)
       .then((text) => {
-        const items = parseJsonl<HistoryEntry & OpsObject>(text);
+        const items = parseResults<HistoryEntry & OpsObject>(text);
         setItems(x => [...x, items]);
 
         setOps(x => {
@@ -0,0 +1,179 @@
+//! Disk usage of a data directory, per file and category of file
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// What a file of a backend's data directory is used for, guessed from its name
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Write-ahead logs and journals (rocksdb `*.log`, fjall journals, nebari transaction log)
+    Wal,
+
+    /// Files holding the actual key-value pairs (including index blocks stored inside them)
+    Data,
+
+    /// Separate index files (sled page table snapshots)
+    Index,
+
+    /// Manifests, locks, config and version markers
+    Metadata,
+
+    Other,
+}
+
+impl Category {
+    fn of(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+
+        let in_dir = |dir: &str| {
+            path.parent()
+                .is_some_and(|parent| parent.components().any(|c| c.as_os_str() == dir))
+        };
+
+        // NOTE: The rocksdb info log is called `LOG`, WALs are called `000123.log`
+        if in_dir("journals") || name.ends_with(".log") || name == "_transactions" {
+            return Self::Wal;
+        }
+
+        if in_dir("segments")
+            || [".sst", ".redb", ".persy", ".nebari"]
+                .iter()
+                .any(|ext| name.ends_with(ext))
+            || matches!(name, "data.mdb" | "data.db" | "db")
+        {
+            return Self::Data;
+        }
+
+        if name.starts_with("snap.") {
+            return Self::Index;
+        }
+
+        if name.starts_with("MANIFEST-")
+            || name.starts_with("OPTIONS-")
+            || matches!(
+                name,
+                "CURRENT"
+                    | "IDENTITY"
+                    | "LOCK"
+                    | "lock.mdb"
+                    | "conf"
+                    | "version"
+                    | "config"
+                    | "levels"
+            )
+        {
+            return Self::Metadata;
+        }
+
+        Self::Other
+    }
+}
+
+/// Size of a file, or of a group of files
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Size {
+    /// Sum of file lengths
+    pub apparent_bytes: u64,
+
+    /// Space actually allocated on disk (`st_blocks`), which is less than the apparent size for sparse files
+    /// and more for preallocated files
+    pub allocated_bytes: u64,
+}
+
+impl Size {
+    fn add(&mut self, other: Size) {
+        self.apparent_bytes += other.apparent_bytes;
+        self.allocated_bytes += other.allocated_bytes;
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileUsage {
+    /// Path relative to the data directory
+    pub path: PathBuf,
+    pub category: Category,
+
+    #[serde(flatten)]
+    pub size: Size,
+}
+
+/// Disk usage of a data directory
+#[derive(Debug, Default)]
+pub struct DiskUsage {
+    pub files: Vec<FileUsage>,
+}
+
+#[cfg(unix)]
+fn allocated_bytes(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    // NOTE: `st_blocks` is always in 512-byte units, regardless of the file system block size
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_bytes(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+impl DiskUsage {
+    /// Walks the given directory (or file)
+    ///
+    /// Files that are deleted while walking (e.g. by compactions) are skipped.
+    pub fn measure(path: &Path) -> std::io::Result<Self> {
+        let mut usage = Self::default();
+        usage.walk(path, path)?;
+        Ok(usage)
+    }
+
+    fn walk(&mut self, root: &Path, path: &Path) -> std::io::Result<()> {
+        let metadata = std::fs::symlink_metadata(path)?;
+
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                match self.walk(root, &entry?.path()) {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    result => result?,
+                }
+            }
+        } else if metadata.is_file() {
+            self.files.push(FileUsage {
+                path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+                category: Category::of(path),
+                size: Size {
+                    apparent_bytes: metadata.len(),
+                    allocated_bytes: allocated_bytes(&metadata),
+                },
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn total(&self) -> Size {
+        let mut total = Size::default();
+
+        for file in &self.files {
+            total.add(file.size);
+        }
+
+        total
+    }
+
+    pub fn by_category(&self) -> BTreeMap<Category, Size> {
+        let mut categories = BTreeMap::<Category, Size>::new();
+
+        for file in &self.files {
+            categories.entry(file.category).or_default().add(file.size);
+        }
+
+        categories
+    }
+}
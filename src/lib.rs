@@ -1,5 +1,9 @@
 use clap::{Parser, ValueEnum};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+pub mod disk_usage;
+pub mod procfs;
+pub mod record;
 
 #[derive(Copy, Eq, PartialEq, Debug, Clone, ValueEnum, Serialize)]
 #[clap(rename_all = "kebab_case")]
@@ -43,7 +47,7 @@ impl std::fmt::Display for Backend {
     }
 }
 
-#[derive(Copy, Debug, Clone, ValueEnum, Serialize, PartialEq, Eq)]
+#[derive(Copy, Debug, Clone, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
 #[clap(rename_all = "kebab_case")]
 pub enum Workload {
     /// Workload A: Update heavy workload
@@ -78,10 +82,68 @@ pub enum Workload {
     TaskG,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, clap::ValueEnum)]
+#[derive(Copy, Debug, Default, Clone, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Arrival {
+    /// Operations are issued at fixed intervals
+    #[default]
+    Constant,
+
+    /// Operations are issued with exponentially distributed inter-arrival times
+    Poisson,
+}
+
+#[derive(Copy, Debug, Default, Clone, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Driver {
+    /// Every worker is an OS thread calling the storage engine directly
+    #[default]
+    Threads,
+
+    /// Every worker is a tokio task, issuing operations through `spawn_blocking`
+    #[cfg(feature = "tokio")]
+    Tokio,
+}
+
+/// What kind of operations a worker thread issues
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Reads and writes, chosen randomly according to the workload's write ratio
+    Mixed,
+
+    /// Only reads
+    Reader,
+
+    /// Only writes
+    Writer,
+}
+
+#[derive(Copy, Debug, Default, Clone, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[clap(rename_all = "kebab_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// Stop the run at the first failed operation
+    #[default]
+    Abort,
+
+    /// Retry a failed operation up to `--error-retries` times, then count it and continue
+    Retry,
+
+    /// Count the failed operation and continue
+    Count,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LsmCompaction {
     Leveled,
     Tiered,
+
+    /// Never merges segments; drops the oldest segments once `fifo_limit` or `fifo_ttl_secs` is exceeded
+    Fifo,
 }
 
 impl std::fmt::Display for LsmCompaction {
@@ -92,13 +154,22 @@ impl std::fmt::Display for LsmCompaction {
             match self {
                 Self::Leveled => "LCS",
                 Self::Tiered => "STCS",
+                Self::Fifo => "FIFO",
             }
         )
     }
 }
 
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got {s:?}"))?;
+
+    Ok((key.trim().to_owned(), value.trim().to_owned()))
+}
+
 /// CLI argument parse
-#[derive(Clone, Parser, Debug)]
+#[derive(Clone, Parser, Debug, Serialize)]
 #[command(author = "marvin-j97", version = env!("CARGO_PKG_VERSION"), about = "Rust KV-store profiler")]
 #[command(propagate_version = true)]
 pub struct Args {
@@ -109,7 +180,29 @@ pub struct Args {
     pub workload: Workload,
 
     #[arg(long, default_value_t = 1)]
-    pub threads: u8,
+    pub threads: u16,
+
+    /// Number of dedicated reader threads (replaces `--threads`)
+    #[arg(long)]
+    pub readers: Option<u16>,
+
+    /// Number of dedicated writer threads (replaces `--threads`)
+    #[arg(long)]
+    pub writers: Option<u16>,
+
+    /// How workers issue operations
+    #[arg(long, value_enum, default_value_t = Driver::Threads)]
+    pub driver: Driver,
+
+    /// Maximum number of threads in tokio's blocking pool (tokio driver only)
+    #[arg(long, default_value_t = 512, value_parser = clap::value_parser!(u16).range(1..))]
+    pub blocking_threads: u16,
+
+    /// Let all threads draw from a single, shared key space
+    ///
+    /// By default, every thread (or writer) works on its own key range, so threads never contend on the same keys.
+    #[arg(long, default_value_t = false)]
+    pub shared_keyspace: bool,
 
     #[arg(long)]
     pub items: u32,
@@ -136,9 +229,34 @@ pub struct Args {
     #[arg(long, default_value_t = 16_000_000)]
     pub cache_size: u32,
 
+    /// Backend-specific tuning option (repeatable), e.g. `--backend-opt write_buffer_size=67108864`
+    ///
+    /// Options the selected backend does not support are rejected.
+    #[arg(long = "backend-opt", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub backend_opts: Vec<(String, String)>,
+
+    /// Map size of the LMDB environment (in bytes, rounded up to the page size)
+    ///
+    /// Limits the size of the data set, unless `--heed-auto-grow` is set.
+    #[arg(long, default_value_t = 8_000_000_000)]
+    pub heed_map_size: u64,
+
+    /// Double the LMDB map size whenever it is full, instead of failing the write
+    #[arg(long, default_value_t = false)]
+    pub heed_auto_grow: bool,
+
+    /// Memory budget of the worker (in bytes)
+    ///
+    /// The worker runs in its own cgroup (v2) with `memory.high` set to the limit if possible,
+    /// otherwise its RSS is monitored. Exceeding the limit ends the run, which is marked as failed.
+    #[arg(long)]
+    pub memory_limit: Option<u64>,
+
     #[arg(long, default_value = "log.jsonl")]
     pub out: String,
 
+    /// Record heap allocations every tick and a heap profile at the end
+    /// (requires the `heap-stats` feature)
     #[arg(long, default_value_t = false)]
     pub snapshot_heap: bool,
 
@@ -147,4 +265,91 @@ pub struct Args {
 
     #[arg(long, default_value_t = 1)]
     pub minutes: u16,
+
+    /// Interval between metrics records (in seconds)
+    ///
+    /// Defaults to the run duration divided by `--samples`. Has to be between 0.001 and 3600.
+    #[arg(long, conflicts_with = "samples")]
+    pub sample_interval: Option<f64>,
+
+    /// Number of metrics records over the run duration, regardless of how long the run is
+    #[arg(long, default_value_t = 120)]
+    pub samples: u32,
+
+    /// Export engine-internal statistics (where the backend provides them)
+    /// as `backend_stats` in every metrics record
+    #[arg(long, default_value_t = false)]
+    pub backend_stats: bool,
+
+    /// A metrics tick counts as a write stall if its write throughput drops below
+    /// this percentage of the average throughput of previous (non-stalled) ticks
+    #[arg(long, default_value_t = 50)]
+    pub stall_threshold_percent: u8,
+
+    /// What to do when a storage engine operation fails
+    ///
+    /// Failed operations are counted per operation type and error kind in the metrics records.
+    #[arg(long, value_enum, default_value_t = ErrorPolicy::Abort)]
+    pub on_error: ErrorPolicy,
+
+    /// Number of retries of a failed operation (with `--on-error retry`)
+    #[arg(long, default_value_t = 3)]
+    pub error_retries: u16,
+
+    /// Target throughput (operations per second, across all threads)
+    ///
+    /// If set, workers run open-loop: operations are issued at the target rate
+    /// and latencies are measured from the intended start time.
+    /// If not set, workers run closed-loop at maximum speed.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ops_per_sec: Option<u32>,
+
+    /// Arrival process used in open-loop mode
+    #[arg(long, value_enum, default_value_t = Arrival::Constant)]
+    pub arrival: Arrival,
+
+    /// Crash-recovery mode: run the workload in a child process, kill it with SIGKILL
+    /// after `--crash-after-secs`, then reopen the database, measuring recovery time
+    /// and checking that every acknowledged synced write survived the process crash
+    #[arg(long, default_value_t = false)]
+    pub crash_recovery: bool,
+
+    /// Seconds after which the child process of a crash-recovery run is killed
+    #[arg(long, default_value_t = 10)]
+    pub crash_after_secs: u16,
+
+    /// Marks the child process of a crash-recovery run
+    #[arg(long, default_value_t = false, hide = true)]
+    pub crash_child: bool,
+
+    /// Open-time benchmark: repeatedly open an existing data set of `--items` items,
+    /// measuring time to open, time to first read and memory after opening,
+    /// with a warm and a cold page cache
+    #[arg(long, default_value_t = false)]
+    pub open_bench: bool,
+
+    /// Number of rounds of the open-time benchmark
+    #[arg(long, default_value_t = 3)]
+    pub open_rounds: u16,
+
+    /// Marks the child process of an open-time benchmark round
+    #[arg(long, default_value_t = false, hide = true)]
+    pub open_child: bool,
+
+    /// Step-load mode: starting at `--ops-per-sec`, increase the target throughput
+    /// every step until the p99 latency exceeds `--slo-p99-us`
+    #[arg(long, default_value_t = false, requires = "ops_per_sec")]
+    pub ramp: bool,
+
+    /// Target throughput increase per ramp step (in percent)
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    pub ramp_step_percent: u16,
+
+    /// Duration of a ramp step (in seconds)
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u16).range(1..))]
+    pub ramp_step_secs: u16,
+
+    /// p99 latency SLO for ramp mode (in microseconds)
+    #[arg(long, default_value_t = 10_000)]
+    pub slo_p99_us: u64,
 }
@@ -0,0 +1,377 @@
+//! Cheap process statistics, read directly from `/proc/self` (Linux only)
+//!
+//! All readers return `None` if the file does not exist (e.g. on other platforms).
+
+use serde::{Deserialize, Serialize};
+
+/// Returns the size of a memory page
+#[cfg(unix)]
+pub fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u64::try_from(size).unwrap_or(4_096)
+}
+
+#[cfg(not(unix))]
+pub fn page_size() -> u64 {
+    4_096
+}
+
+/// Returns the number of clock ticks per second, the unit of CPU times in `/proc`
+#[cfg(unix)]
+pub fn clock_ticks() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    u64::try_from(ticks).unwrap_or(100)
+}
+
+#[cfg(not(unix))]
+pub fn clock_ticks() -> u64 {
+    100
+}
+
+/// I/O counters of `/proc/self/io`
+///
+/// `rchar`/`wchar` count logical I/O (every byte passed to read/write syscalls, including page cache hits),
+/// `read_bytes`/`write_bytes` count physical I/O (bytes fetched from or sent to the block layer).
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct IoCounters {
+    pub rchar: u64,
+    pub wchar: u64,
+    pub syscr: u64,
+    pub syscw: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+
+    /// Bytes that were written to the page cache, but truncated or deleted before being written back
+    pub cancelled_write_bytes: u64,
+}
+
+impl IoCounters {
+    pub fn read() -> Option<Self> {
+        let content = std::fs::read_to_string("/proc/self/io").ok()?;
+
+        let mut counters = Self::default();
+
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            let value = value.trim().parse().ok()?;
+
+            match key {
+                "rchar" => counters.rchar = value,
+                "wchar" => counters.wchar = value,
+                "syscr" => counters.syscr = value,
+                "syscw" => counters.syscw = value,
+                "read_bytes" => counters.read_bytes = value,
+                "write_bytes" => counters.write_bytes = value,
+                "cancelled_write_bytes" => counters.cancelled_write_bytes = value,
+                _ => {}
+            }
+        }
+
+        Some(counters)
+    }
+
+    /// Bytes actually written to storage
+    pub fn physical_write_bytes(&self) -> u64 {
+        self.write_bytes.saturating_sub(self.cancelled_write_bytes)
+    }
+
+    pub fn delta(&self, prev: &Self) -> Self {
+        Self {
+            rchar: self.rchar - prev.rchar,
+            wchar: self.wchar - prev.wchar,
+            syscr: self.syscr - prev.syscr,
+            syscw: self.syscw - prev.syscw,
+            read_bytes: self.read_bytes - prev.read_bytes,
+            write_bytes: self.write_bytes - prev.write_bytes,
+            cancelled_write_bytes: self.cancelled_write_bytes - prev.cancelled_write_bytes,
+        }
+    }
+}
+
+/// Selected fields of `/proc/self/stat`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcStat {
+    pub minor_faults: u64,
+    pub major_faults: u64,
+
+    /// CPU time spent in user mode (in clock ticks)
+    pub utime: u64,
+
+    /// CPU time spent in kernel mode (in clock ticks)
+    pub stime: u64,
+
+    pub threads: u64,
+    pub vsize_bytes: u64,
+    pub rss_bytes: u64,
+}
+
+/// Splits a `stat` file into the command name (field 2) and a getter for numeric fields (by their number in proc(5))
+fn parse_stat(content: &str) -> Option<(&str, impl Fn(usize) -> Option<u64> + '_)> {
+    // NOTE: The command name may contain spaces and parentheses,
+    // so fields are counted from the last closing parenthesis
+    let (head, rest) = content.rsplit_once(')')?;
+    let (_, name) = head.split_once('(')?;
+    let fields = rest.split_whitespace().collect::<Vec<_>>();
+
+    // NOTE: `fields[0]` is field 3 (state)
+    let field = move |n: usize| -> Option<u64> { fields.get(n - 3)?.parse().ok() };
+
+    Some((name, field))
+}
+
+impl ProcStat {
+    pub fn read() -> Option<Self> {
+        let content = std::fs::read_to_string("/proc/self/stat").ok()?;
+        let (_, field) = parse_stat(&content)?;
+
+        Some(Self {
+            minor_faults: field(10)?,
+            major_faults: field(12)?,
+            utime: field(14)?,
+            stime: field(15)?,
+            threads: field(20)?,
+            vsize_bytes: field(23)?,
+            rss_bytes: field(24)? * page_size(),
+        })
+    }
+
+    /// CPU usage of the process between two samples (in percent of one core)
+    pub fn cpu_percent(&self, prev: &Self, elapsed_secs: f64) -> f64 {
+        cpu_percent(
+            (self.utime + self.stime).saturating_sub(prev.utime + prev.stime),
+            elapsed_secs,
+        )
+    }
+}
+
+/// Converts CPU time from clock ticks to seconds
+pub fn ticks_to_secs(ticks: u64) -> f64 {
+    ticks as f64 / clock_ticks() as f64
+}
+
+/// Converts CPU time (in clock ticks) spent during `elapsed_secs` to percent of one core
+pub fn cpu_percent(ticks: u64, elapsed_secs: f64) -> f64 {
+    ticks as f64 / clock_ticks() as f64 / elapsed_secs * 100.0
+}
+
+/// CPU time and context switches of a thread, from `/proc/self/task/<tid>/{stat,status}`
+#[derive(Clone, Debug, Default)]
+pub struct TaskStat {
+    pub tid: u64,
+
+    /// Thread name (truncated to 15 bytes by the kernel)
+    pub name: String,
+
+    /// CPU time spent in user mode (in clock ticks)
+    pub utime: u64,
+
+    /// CPU time spent in kernel mode (in clock ticks)
+    pub stime: u64,
+
+    pub voluntary_ctxt_switches: u64,
+    pub involuntary_ctxt_switches: u64,
+}
+
+impl TaskStat {
+    /// Reads all threads of the process
+    ///
+    /// Threads that exit while reading are skipped.
+    pub fn read_all() -> Option<Vec<Self>> {
+        let tasks = std::fs::read_dir("/proc/self/task").ok()?;
+
+        Some(
+            tasks
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let tid = entry.file_name().to_str()?.parse().ok()?;
+                    Self::read(tid, &entry.path())
+                })
+                .collect(),
+        )
+    }
+
+    fn read(tid: u64, path: &std::path::Path) -> Option<Self> {
+        let stat = std::fs::read_to_string(path.join("stat")).ok()?;
+        let (name, field) = parse_stat(&stat)?;
+
+        let mut task = Self {
+            tid,
+            name: name.to_owned(),
+            utime: field(14)?,
+            stime: field(15)?,
+            ..Default::default()
+        };
+
+        let status = std::fs::read_to_string(path.join("status")).ok()?;
+
+        for line in status.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            match key {
+                "voluntary_ctxt_switches" => {
+                    task.voluntary_ctxt_switches = value.trim().parse().ok()?;
+                }
+                "nonvoluntary_ctxt_switches" => {
+                    task.involuntary_ctxt_switches = value.trim().parse().ok()?;
+                }
+                _ => {}
+            }
+        }
+
+        Some(task)
+    }
+}
+
+/// Returns the value of a `Key:   123 kB` line (in bytes)
+fn kb_field(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (k, value) = line.split_once(':')?;
+
+        if k != key {
+            return None;
+        }
+
+        let kb: u64 = value.trim().trim_end_matches("kB").trim().parse().ok()?;
+        Some(kb * 1_024)
+    })
+}
+
+/// Breakdown of the process memory, from `/proc/self/status` and `/proc/self/smaps_rollup`
+///
+/// Pages of memory-mapped files (e.g. LMDB) count as file-backed,
+/// so they can be told apart from heap memory (anonymous).
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct MemoryStat {
+    pub rss_bytes: u64,
+
+    /// Highest RSS so far
+    pub peak_rss_bytes: u64,
+
+    /// Heap, stacks and anonymous mappings
+    pub anon_bytes: u64,
+
+    /// Memory-mapped files and the executable
+    pub file_bytes: u64,
+
+    pub shmem_bytes: u64,
+    pub swap_bytes: u64,
+
+    /// Proportional set size, where shared pages are divided among all processes mapping them
+    /// (`None` if `smaps_rollup` is not available)
+    pub pss_bytes: Option<u64>,
+    pub pss_anon_bytes: Option<u64>,
+    pub pss_file_bytes: Option<u64>,
+
+    /// Modified pages that are only mapped by this process
+    pub private_dirty_bytes: Option<u64>,
+}
+
+impl MemoryStat {
+    pub fn read() -> Option<Self> {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+
+        // NOTE: smaps_rollup needs Linux 4.14+
+        let rollup = std::fs::read_to_string("/proc/self/smaps_rollup").unwrap_or_default();
+
+        Some(Self {
+            rss_bytes: kb_field(&status, "VmRSS")?,
+            peak_rss_bytes: kb_field(&status, "VmHWM")?,
+            anon_bytes: kb_field(&status, "RssAnon")?,
+            file_bytes: kb_field(&status, "RssFile")?,
+            shmem_bytes: kb_field(&status, "RssShmem")?,
+            swap_bytes: kb_field(&status, "VmSwap").unwrap_or_default(),
+            pss_bytes: kb_field(&rollup, "Pss"),
+            pss_anon_bytes: kb_field(&rollup, "Pss_Anon"),
+            pss_file_bytes: kb_field(&rollup, "Pss_File"),
+            private_dirty_bytes: kb_field(&rollup, "Private_Dirty"),
+        })
+    }
+}
+
+/// A mounted file system, from `/proc/self/mountinfo`
+#[derive(Clone, Debug)]
+pub struct Mount {
+    pub mount_point: std::path::PathBuf,
+
+    /// Per-mount options (e.g. `rw,noatime`)
+    pub options: String,
+
+    pub fs_type: String,
+
+    /// Device, or e.g. `tmpfs`
+    pub source: String,
+
+    /// Per-superblock options (e.g. `rw,discard`)
+    pub super_options: String,
+}
+
+/// Undoes the octal escaping of spaces, tabs, newlines and backslashes in mountinfo paths
+fn unescape_mount_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(idx) = rest.find('\\') {
+        out.push_str(&rest[..idx]);
+
+        let escaped = rest.get(idx + 1..idx + 4);
+
+        match escaped.and_then(|x| u8::from_str_radix(x, 8).ok()) {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[idx + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[idx + 1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+impl Mount {
+    pub fn read_all() -> Option<Vec<Self>> {
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+
+        Some(
+            mountinfo
+                .lines()
+                .filter_map(|line| {
+                    // NOTE: A variable number of optional fields precedes the ` - ` separator
+                    let (mount, fs) = line.split_once(" - ")?;
+                    let mut mount = mount.split_whitespace().skip(4);
+                    let mut fs = fs.split_whitespace();
+
+                    Some(Self {
+                        mount_point: unescape_mount_path(mount.next()?).into(),
+                        options: mount.next()?.to_owned(),
+                        fs_type: fs.next()?.to_owned(),
+                        source: fs.next()?.to_owned(),
+                        super_options: fs.next().unwrap_or_default().to_owned(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the mount the given path is on
+    pub fn of(path: &std::path::Path) -> Option<Self> {
+        let path = std::fs::canonicalize(path).ok()?;
+
+        // NOTE: Later mounts shadow earlier ones on the same mount point,
+        // and `max_by_key` returns the last of equal elements
+        Self::read_all()?
+            .into_iter()
+            .filter(|mount| path.starts_with(&mount.mount_point))
+            .max_by_key(|mount| mount.mount_point.components().count())
+    }
+}
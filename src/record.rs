@@ -0,0 +1,871 @@
+//! Records of the JSONL result file
+//!
+//! Every line of a result file is one record, tagged by its `type` field:
+//! `system` and `setup` come first, followed by periodic `metrics` records;
+//! the other records are written by specific modes (e.g. `--ramp`, `--open-bench`) or at the end of a run.
+//! Record types added by a later version of the benchmark are read as [`Record::Other`].
+//!
+//! The format is described by `schema/result.schema.json`; whenever a field is renamed, removed or changes its meaning,
+//! [`SCHEMA_VERSION`] is increased. Adding fields does not change the version.
+
+use crate::{
+    disk_usage::{Category, FileUsage, Size},
+    procfs::{IoCounters, MemoryStat},
+    Arrival, Driver, ErrorPolicy, LsmCompaction, Role, Workload,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::BufRead};
+
+/// Version of the result format, written into every record
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    System(Box<SystemRecord>),
+    Setup(Box<SetupRecord>),
+    Metrics(Box<MetricsRecord>),
+    RampStep(Box<RampStepRecord>),
+    RampSummary(Box<RampSummaryRecord>),
+    Recovery(Box<RecoveryRecord>),
+    Open(Box<OpenRecord>),
+    HeedResize(Box<HeedResizeRecord>),
+    MemoryLimit(Box<MemoryLimitRecord>),
+    Stalls(Box<StallsRecord>),
+    DiskUsage(Box<DiskUsageRecord>),
+    HeapProfile(Box<HeapProfileRecord>),
+    Syncs(Box<SyncsRecord>),
+    Errors(Box<ErrorsRecord>),
+
+    /// Any other record
+    #[serde(other)]
+    Other,
+}
+
+/// Machine the benchmark runs on (first record)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SystemRecord {
+    /// `0` for results written before the format was versioned
+    #[serde(default)]
+    pub schema_version: u32,
+
+    /// Unix timestamp (in microseconds)
+    pub time_micro: u64,
+
+    pub os: Option<String>,
+    pub kernel: Option<String>,
+
+    /// CPU model
+    pub cpu: String,
+
+    /// Total memory (in bytes)
+    pub mem: u64,
+}
+
+/// Benchmark configuration (second record)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetupRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+
+    /// Backend, including its version (and compaction strategy for fjall)
+    pub backend: String,
+    pub workload: Workload,
+    pub threads: u16,
+    pub items: u32,
+    pub value_size: u32,
+    pub cache_size_in_bytes: u32,
+
+    // NOTE: The fields below are missing in results written before the format was versioned;
+    // their defaults describe how those runs behaved, unknown settings are `None`
+    #[serde(default)]
+    pub readers: Option<u16>,
+    #[serde(default)]
+    pub writers: Option<u16>,
+    #[serde(default)]
+    pub shared_keyspace: bool,
+    #[serde(default)]
+    pub driver: Driver,
+    #[serde(default)]
+    pub lsm_compaction: Option<LsmCompaction>,
+    #[serde(default)]
+    pub lsm_block_size: Option<u16>,
+    #[serde(default)]
+    pub backend_opts: BTreeMap<String, String>,
+
+    /// Target rate, or `None` for a closed-loop run
+    #[serde(default)]
+    pub ops_per_sec: Option<u32>,
+    #[serde(default)]
+    pub arrival: Arrival,
+    #[serde(default)]
+    pub ramp: bool,
+    #[serde(default)]
+    pub backend_stats: bool,
+    #[serde(default)]
+    pub on_error: ErrorPolicy,
+    #[serde(default)]
+    pub snapshot_heap: bool,
+    #[serde(default)]
+    pub memory_limit: Option<u64>,
+    #[serde(default)]
+    pub sample_interval_secs: Option<f64>,
+    #[serde(default)]
+    pub sync_trace: bool,
+
+    /// All command line arguments, including defaults
+    #[serde(default)]
+    pub config: Option<serde_json::Value>,
+
+    #[serde(default)]
+    pub build: Option<BuildInfo>,
+
+    /// File system of the data directory (`None` if it cannot be determined)
+    #[serde(default)]
+    pub filesystem: Option<FilesystemInfo>,
+}
+
+/// How the worker binary was built
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Version of the benchmark crate
+    pub version: String,
+
+    /// `None` if not built from a git checkout
+    pub git_commit: Option<String>,
+
+    /// Whether tracked files had uncommitted changes
+    pub git_dirty: Option<bool>,
+
+    pub rustc: Option<String>,
+
+    /// Cargo profile (`debug` or `release`)
+    pub profile: String,
+    pub target: String,
+    pub features: Vec<String>,
+
+    /// Locked versions of the storage engine crates
+    pub crates: BTreeMap<String, String>,
+}
+
+/// Mount the data directory is on, from `/proc/self/mountinfo`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FilesystemInfo {
+    pub mount_point: String,
+    pub fs_type: String,
+    pub source: String,
+    pub mount_options: String,
+    pub super_options: String,
+}
+
+/// Operations and throughput of a single worker thread
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkerMetrics {
+    pub thread: usize,
+    pub role: Role,
+    pub write_ops: u64,
+    pub read_ops: u64,
+
+    /// Throughput since the previous record
+    pub ops_per_sec: f64,
+}
+
+/// Failed operations per error kind (`map_full`, `disk_full`, `conflict`, `corruption`, `io`, `other`)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ErrorMetrics {
+    pub write: BTreeMap<String, u64>,
+    pub read: BTreeMap<String, u64>,
+}
+
+/// Who a thread's CPU time is attributed to
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadKind {
+    /// Foreground threads running database operations
+    Worker,
+
+    /// Threads of the benchmark itself (main, metrics, ...)
+    Harness,
+
+    /// Threads spawned by the storage engine (compaction, flushes, ...)
+    Engine,
+}
+
+/// CPU usage (in percent of one core) per kind of thread
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ThreadCpu {
+    pub worker: f64,
+    pub engine: f64,
+    pub harness: f64,
+}
+
+/// CPU time and context switches of a single thread
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThreadSample {
+    pub tid: u64,
+
+    /// Thread name (truncated to 15 bytes by the kernel)
+    pub name: String,
+    pub kind: ThreadKind,
+
+    /// CPU usage since the previous record (in percent of one core)
+    pub cpu: f64,
+
+    /// Cumulative CPU time
+    pub user_secs: f64,
+    pub system_secs: f64,
+
+    /// Cumulative context switches
+    pub voluntary_ctxt_switches: u64,
+    pub involuntary_ctxt_switches: u64,
+}
+
+/// Per-thread CPU usage and context switches since the previous record
+///
+/// Threads that exited since the previous record are not accounted for.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThreadMetrics {
+    pub count: usize,
+    pub cpu: ThreadCpu,
+    pub voluntary_ctxt_switches: u64,
+    pub involuntary_ctxt_switches: u64,
+    pub per_thread: Vec<ThreadSample>,
+}
+
+/// Write stall state of a single metrics record
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StallState {
+    /// Whether write throughput dropped below the threshold, or the engine reported a stall
+    pub stalled: bool,
+    pub throughput: bool,
+    pub engine: bool,
+
+    /// Average write throughput of all previous non-stalled records
+    pub baseline_write_ops_per_sec: Option<f64>,
+
+    /// Engine-reported compaction debt (`None` if the engine does not report it)
+    pub pending_compaction_bytes: Option<u64>,
+    pub l0_segments: Option<u64>,
+}
+
+/// Calls and latency distribution of one kind of sync (`fsync`, `fdatasync`, `sync_file_range`, `msync`)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SyncStats {
+    pub calls: u64,
+    pub total_micros: u64,
+    pub avg_micros: u64,
+    pub p50_micros: u64,
+    pub p90_micros: u64,
+    pub p99_micros: u64,
+    pub p999_micros: u64,
+    pub max_micros: u64,
+}
+
+/// Allocator counters, and allocation rates since the previous record
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HeapMetrics {
+    pub live_bytes: u64,
+    pub peak_bytes: u64,
+    pub live_allocations: u64,
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub reallocations: u64,
+    pub allocated_total_bytes: u64,
+    pub allocations_per_sec: f64,
+    pub allocated_bytes_per_sec: f64,
+}
+
+/// Periodic sample, written every `--sample-interval`
+///
+/// Operation counts are cumulative, rates (`*_per_sec`, `cpu`) refer to the time since the previous record.
+/// Fields that cannot be measured on the current platform are `null`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetricsRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub write_ops: u64,
+    pub read_ops: u64,
+    pub delete_ops: u64,
+    pub scan_ops: u64,
+
+    #[serde(default)]
+    pub ops_per_sec: f64,
+    #[serde(default)]
+    pub write_ops_per_sec: f64,
+    #[serde(default)]
+    pub read_ops_per_sec: f64,
+    #[serde(default)]
+    pub delete_ops_per_sec: f64,
+    #[serde(default)]
+    pub scan_ops_per_sec: f64,
+
+    /// Average latency since the previous record (in microseconds)
+    pub avg_write_latency: u64,
+    pub avg_read_latency: u64,
+
+    #[serde(default)]
+    pub write_errors: u64,
+    #[serde(default)]
+    pub read_errors: u64,
+    #[serde(default)]
+    pub errors: ErrorMetrics,
+    #[serde(default)]
+    pub retries: u64,
+
+    #[serde(default)]
+    pub per_thread: Vec<WorkerMetrics>,
+
+    /// CPU usage of the whole machine (in percent)
+    pub cpu: f32,
+
+    /// CPU usage of the benchmark process (in percent of one core)
+    #[serde(default)]
+    pub process_cpu: Option<f64>,
+    #[serde(default)]
+    pub process_cpu_user: Option<f64>,
+    #[serde(default)]
+    pub process_cpu_system: Option<f64>,
+    #[serde(default)]
+    pub process_cpu_user_secs: Option<f64>,
+    #[serde(default)]
+    pub process_cpu_system_secs: Option<f64>,
+
+    /// Per-thread CPU usage and context switches
+    #[serde(default)]
+    pub threads: Option<ThreadMetrics>,
+
+    /// Resident set size
+    pub mem_bytes: f32,
+    pub mem_mib: f32,
+
+    /// RSS split into anonymous, file-backed and shared memory
+    #[serde(default)]
+    pub memory: Option<MemoryStat>,
+    #[serde(default)]
+    pub minor_faults: Option<u64>,
+    #[serde(default)]
+    pub major_faults: Option<u64>,
+    #[serde(default)]
+    pub vsize_bytes: Option<u64>,
+    #[serde(default)]
+    pub os_threads: Option<u64>,
+
+    /// Physical I/O
+    pub disk_bytes_w: u64,
+    pub disk_bytes_r: u64,
+    pub disk_mib_w: f32,
+    pub disk_mib_r: f32,
+
+    /// Raw counters of `/proc/self/io`, and their change since the previous record
+    #[serde(default)]
+    pub io: Option<IoCounters>,
+    #[serde(default)]
+    pub io_delta: Option<IoCounters>,
+
+    /// Space allocated by the data directory
+    pub du_bytes: u64,
+    pub du_mib: f32,
+
+    /// Sum of file lengths in the data directory
+    #[serde(default)]
+    pub apparent_bytes: u64,
+
+    /// Apparent and allocated size per category of file
+    #[serde(default)]
+    pub disk_usage: Option<BTreeMap<Category, Size>>,
+
+    /// Amplifications are `None` as long as nothing was written
+    pub space_amp: Option<f64>,
+    pub write_amp: Option<f64>,
+    #[serde(default)]
+    pub logical_write_amp: Option<f64>,
+
+    /// Size of all written keys and values
+    pub dataset_size: f64,
+
+    /// Write stall state
+    #[serde(default)]
+    pub stall: Option<StallState>,
+
+    /// Syncs per function (`sync-trace` feature)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syncs: Option<BTreeMap<String, SyncStats>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syncs_per_write: Option<f64>,
+
+    /// Allocator counters (`--snapshot-heap`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heap: Option<HeapMetrics>,
+
+    /// Engine-internal statistics (`--backend-stats`)
+    ///
+    /// Kept as a JSON value, because every engine reports different statistics.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend_stats: Option<serde_json::Value>,
+
+    /// Scheduling delay of the open-loop driver (in microseconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_scheduling_delay: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p99_scheduling_delay: Option<u64>,
+}
+
+/// One step of `--ramp`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RampStepRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    /// Starts at `1`
+    pub step: u32,
+    pub target_ops_per_sec: u32,
+    pub achieved_ops_per_sec: f64,
+
+    /// Latencies of all operations of the step (in microseconds)
+    pub p50_latency: u64,
+    pub p99_latency: u64,
+    pub max_latency: u64,
+    pub slo_p99_latency: u64,
+
+    /// Whether the p99 latency stayed within the SLO
+    pub ok: bool,
+}
+
+/// Why a ramp ended
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RampStop {
+    /// The run ended (`--minutes`) before the SLO was violated
+    Timeout,
+
+    /// A step violated the latency SLO
+    Slo,
+}
+
+/// Result of `--ramp`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RampSummaryRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub steps: u32,
+
+    /// Highest target throughput that stayed within the latency SLO
+    pub max_sustainable_ops_per_sec: Option<u32>,
+    pub slo_p99_latency: u64,
+    pub stopped_by: RampStop,
+}
+
+/// Result of `--crash-recovery`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecoveryRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    /// Whether writes were synced (`--fsync`)
+    ///
+    /// The child is killed with SIGKILL, which keeps the OS page cache,
+    /// so this checks process-crash consistency, not durability on power loss.
+    pub fsync: bool,
+    pub crash_after_secs: u16,
+
+    /// Time to reopen the database after the crash
+    pub recovery_micros: u64,
+
+    /// Size of the write-ahead log to replay (`None` for engines without a separate log)
+    pub wal_bytes: Option<u64>,
+    pub du_bytes: Option<u64>,
+
+    pub acked_writes: usize,
+    pub acked_keys: usize,
+
+    /// Acknowledged keys that were missing or had an older value after recovery
+    pub lost_keys: usize,
+    pub missing_keys: usize,
+    pub stale_keys: usize,
+
+    /// Up to 10 lost keys (hex-encoded)
+    pub lost_key_samples: Vec<String>,
+}
+
+/// State of the page cache when opening the database
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageCache {
+    /// The data set was just closed
+    Warm,
+
+    /// Evicted using `/proc/sys/vm/drop_caches`
+    DropCaches,
+
+    /// Evicted using `posix_fadvise` (without root)
+    Fadvise,
+
+    /// Evicting is not supported on this platform
+    None,
+}
+
+/// One round of `--open-bench`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpenRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub items: u32,
+    pub value_size: u32,
+    pub round: u16,
+    pub cold: bool,
+    pub cache: PageCache,
+
+    pub open_micros: u64,
+
+    /// Time from starting to open the database until the first read returned
+    pub first_read_micros: u64,
+
+    /// Memory of the (fresh) child process after the first read
+    pub mem_bytes: u64,
+    pub du_bytes: Option<u64>,
+}
+
+/// Resize of the LMDB memory map (`--heed-auto-grow`)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeedResizeRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub from_bytes: u64,
+    pub to_bytes: u64,
+
+    /// Time from hitting `MapFull` until the map was resized
+    pub stall_micros: u64,
+}
+
+/// How `--memory-limit` was enforced
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryLimitMode {
+    /// `memory.high` of a cgroup (v2)
+    Cgroup,
+
+    /// Polling the RSS of the process
+    Rss,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Ok,
+    Failed,
+}
+
+/// The point at which the memory limit was exceeded
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryLimitExceeded {
+    pub time_micro: u64,
+    pub elapsed_secs: f64,
+    pub usage_bytes: u64,
+    pub write_ops: u64,
+    pub read_ops: u64,
+}
+
+/// Result of `--memory-limit`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryLimitRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub limit_bytes: u64,
+    pub mode: MemoryLimitMode,
+
+    /// `failed` if the limit was exceeded
+    pub status: RunStatus,
+    pub peak_bytes: u64,
+    pub exceeded: Option<MemoryLimitExceeded>,
+}
+
+/// A period of consecutive stalled metrics records
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StallPeriod {
+    pub start_micro: u64,
+    pub duration_secs: f64,
+    pub min_write_ops_per_sec: f64,
+
+    /// Whether write throughput dropped below the threshold
+    pub throughput: bool,
+
+    /// Whether the engine reported a write stall
+    pub engine: bool,
+}
+
+/// All write stalls of a run (end of run)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StallsRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub threshold_percent: f64,
+    pub baseline_write_ops_per_sec: Option<f64>,
+    pub stall_count: usize,
+    pub stalled_secs: f64,
+    pub longest_stall_secs: f64,
+
+    /// `None` if the engine does not report stall times
+    pub engine_stall_secs: Option<f64>,
+    pub max_pending_compaction_bytes: Option<u64>,
+    pub max_l0_segments: Option<u64>,
+    pub periods: Vec<StallPeriod>,
+}
+
+/// Files of the data directory (end of run)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiskUsageRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub total: Size,
+    pub categories: BTreeMap<Category, Size>,
+    pub files: Vec<FileUsage>,
+}
+
+/// Allocations of one size class
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SizeClassUsage {
+    /// Largest allocation size of the class (a power of two)
+    pub max_size: u64,
+
+    /// Allocations made so far
+    pub allocations: u64,
+
+    pub live_count: u64,
+    pub live_bytes: u64,
+}
+
+/// Heap usage per allocation size (`--snapshot-heap`, end of run)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeapProfileRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub live_bytes: u64,
+    pub peak_bytes: u64,
+    pub allocations: u64,
+
+    /// Size classes with at least one allocation
+    pub size_classes: Vec<SizeClassUsage>,
+}
+
+/// All syncs of a run (`sync-trace` feature, end of run)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SyncsRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    pub write_ops: u64,
+    pub syncs: u64,
+
+    /// `None` as long as nothing was written
+    pub syncs_per_write: Option<f64>,
+    pub latency: BTreeMap<String, SyncStats>,
+}
+
+/// Failed operations of a run (end of run)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorsRecord {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub time_micro: u64,
+    pub backend: String,
+
+    /// Failed operations per error kind
+    pub write_errors: BTreeMap<String, u64>,
+    pub read_errors: BTreeMap<String, u64>,
+    pub retries: u64,
+
+    /// The error that aborted the run (with `--on-error abort`)
+    pub aborted_by: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(std::io::Error),
+
+    /// A line is not a valid record
+    Json {
+        line: usize,
+        source: serde_json::Error,
+    },
+
+    /// A record was written by a newer version of the benchmark
+    UnsupportedVersion {
+        line: usize,
+        version: u64,
+    },
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read results: {e}"),
+            Self::Json { line, source } => write!(f, "invalid record in line {line}: {source}"),
+            Self::UnsupportedVersion { line, version } => write!(
+                f,
+                "record in line {line} has schema version {version}, but only up to {SCHEMA_VERSION} is supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Json { source, .. } => Some(source),
+            Self::UnsupportedVersion { .. } => None,
+        }
+    }
+}
+
+/// Reads all records of a result file, skipping empty lines
+///
+/// Results written before the format was versioned are read on a best-effort basis.
+pub fn read_records<R: BufRead>(reader: R) -> impl Iterator<Item = Result<Record, ReadError>> {
+    reader.lines().enumerate().filter_map(|(idx, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(ReadError::Io(e))),
+        };
+
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        let line_no = idx + 1;
+
+        let json = match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(json) => json,
+            Err(source) => {
+                return Some(Err(ReadError::Json {
+                    line: line_no,
+                    source,
+                }))
+            }
+        };
+
+        let version = json["schema_version"].as_u64().unwrap_or_default();
+
+        if version > u64::from(SCHEMA_VERSION) {
+            return Some(Err(ReadError::UnsupportedVersion {
+                line: line_no,
+                version,
+            }));
+        }
+
+        Some(
+            serde_json::from_value(json).map_err(|source| ReadError::Json {
+                line: line_no,
+                source,
+            }),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records as written before the format was versioned
+    const LEGACY: &str = r#"{"cpu":"AMD Ryzen 7 5800X","kernel":"6.1.0","mem":33554432000,"os":"Linux 12 Debian","time_micro":1717000000000000,"type":"system"}
+{"backend":"fjall 1.0.0 (leveled)","cache_size_in_bytes":16000000,"items":100,"threads":1,"time_micro":1717000000001000,"type":"setup","value_size":64,"workload":"TaskA"}
+{"avg_read_latency":3,"avg_write_latency":12,"backend":"fjall 1.0.0 (leveled)","cpu":12.5,"dataset_size":7200.0,"delete_ops":0,"disk_bytes_r":0,"disk_bytes_w":8192,"disk_mib_r":0.0,"disk_mib_w":0.0078125,"du_bytes":4096,"du_mib":0.00390625,"mem_bytes":10485760.0,"mem_mib":10.0,"read_ops":50,"scan_ops":0,"space_amp":null,"time_micro":1717000001000000,"type":"metrics","write_amp":1.1377778,"write_ops":100}
+"#;
+
+    #[test]
+    fn read_legacy_records() {
+        let records = read_records(LEGACY.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let [Record::System(system), Record::Setup(setup), Record::Metrics(metrics)] =
+            records.as_slice()
+        else {
+            panic!("unexpected records: {records:?}");
+        };
+
+        assert_eq!(0, system.schema_version);
+        assert_eq!("AMD Ryzen 7 5800X", system.cpu);
+
+        assert_eq!(0, setup.schema_version);
+        assert_eq!(Workload::TaskA, setup.workload);
+        assert_eq!(Driver::Threads, setup.driver);
+        assert_eq!(ErrorPolicy::Abort, setup.on_error);
+        assert_eq!(None, setup.ops_per_sec);
+        assert_eq!(None, setup.sample_interval_secs);
+        assert!(setup.build.is_none());
+
+        assert_eq!(100, metrics.write_ops);
+        assert_eq!(None, metrics.space_amp);
+        assert_eq!(Some(1.1377778), metrics.write_amp);
+        assert!(metrics.per_thread.is_empty());
+    }
+
+    #[test]
+    fn read_summary_records() {
+        let lines = r#"{"type":"errors","schema_version":1,"time_micro":0,"backend":"redb","write_errors":{"io":2},"read_errors":{},"retries":0,"aborted_by":null}
+{"type":"disk_usage","schema_version":1,"time_micro":0,"backend":"redb","total":{"apparent_bytes":10,"allocated_bytes":4096},"categories":{"data":{"apparent_bytes":10,"allocated_bytes":4096}},"files":[{"path":"db","category":"data","apparent_bytes":10,"allocated_bytes":4096}]}
+{"type":"compaction","schema_version":1,"time_micro":0}
+"#;
+
+        let records = read_records(lines.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let [Record::Errors(errors), Record::DiskUsage(usage), Record::Other] = records.as_slice()
+        else {
+            panic!("unexpected records: {records:?}");
+        };
+
+        assert_eq!(Some(&2), errors.write_errors.get("io"));
+        assert_eq!(None, errors.aborted_by);
+        assert_eq!(4096, usage.categories[&Category::Data].allocated_bytes);
+        assert_eq!(Category::Data, usage.files[0].category);
+        assert_eq!(10, usage.files[0].size.apparent_bytes);
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let line = format!(
+            r#"{{"type":"system","schema_version":{},"time_micro":0,"cpu":"","mem":0}}"#,
+            SCHEMA_VERSION + 1
+        );
+
+        let result = read_records(line.as_bytes()).next().unwrap();
+        assert!(matches!(
+            result,
+            Err(ReadError::UnsupportedVersion { line: 1, .. })
+        ));
+    }
+}
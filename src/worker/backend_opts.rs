@@ -0,0 +1,90 @@
+use clap::CommandFactory;
+use rust_storage_bench::{Args, Backend};
+use std::{fmt::Display, str::FromStr};
+
+/// Options passed with `--backend-opt key=value`, interpreted by the backend adapter
+///
+/// The adapter asks for every option it supports; afterwards, [`BackendOpts::finish`]
+/// rejects any option it did not ask for.
+pub struct BackendOpts<'a> {
+    backend: Backend,
+    opts: &'a [(String, String)],
+
+    /// Options supported by the backend
+    known: Vec<&'static str>,
+}
+
+/// Exits with a usage error, like clap does for invalid arguments
+fn reject(message: String) -> ! {
+    Args::command()
+        .error(clap::error::ErrorKind::InvalidValue, message)
+        .exit()
+}
+
+impl<'a> BackendOpts<'a> {
+    pub fn new(args: &'a Args) -> Self {
+        Self {
+            backend: args.backend,
+            opts: &args.backend_opts,
+            known: vec![],
+        }
+    }
+
+    /// Returns the raw value of an option, if it was given (the last one wins)
+    pub fn get_str(&mut self, key: &'static str) -> Option<&'a str> {
+        self.known.push(key);
+
+        self.opts
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the parsed value of an option, if it was given
+    pub fn get<T: FromStr>(&mut self, key: &'static str) -> Option<T>
+    where
+        T::Err: Display,
+    {
+        let value = self.get_str(key)?;
+
+        match value.parse() {
+            Ok(value) => Some(value),
+            Err(e) => reject(format!(
+                "invalid value {value:?} for backend option '{key}': {e}"
+            )),
+        }
+    }
+
+    /// Returns the value of an option that has to be one of the given choices
+    pub fn get_choice(&mut self, key: &'static str, choices: &[&str]) -> Option<&'a str> {
+        let value = self.get_str(key)?;
+
+        if !choices.contains(&value) {
+            reject(format!(
+                "invalid value {value:?} for backend option '{key}', expected one of: {}",
+                choices.join(", ")
+            ));
+        }
+
+        Some(value)
+    }
+
+    /// Rejects all options that are not supported by the backend
+    pub fn finish(self) {
+        for (key, _) in self.opts {
+            if !self.known.contains(&key.as_str()) {
+                let expected = if self.known.is_empty() {
+                    "none".to_owned()
+                } else {
+                    self.known.join(", ")
+                };
+
+                reject(format!(
+                    "unknown backend option '{key}' for {}, supported options: {expected}",
+                    self.backend
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::db::GenericDatabase;
+use serde_json::json;
+
+/// Number of L0 segments above which fjall stalls writes (see `PartitionHandle::check_write_stall`)
+const FJALL_L0_STALL_THRESHOLD: usize = 20;
+
+/// Engine-reported compaction state of LSM backends
+pub struct CompactionState {
+    /// Whether the engine is currently slowing down or stopping writes
+    pub write_stall: bool,
+
+    /// Total time the engine has stalled writes so far (in seconds)
+    pub cumulative_stall_secs: Option<f64>,
+
+    /// Estimated bytes that still need to be compacted
+    pub pending_compaction_bytes: Option<u64>,
+
+    /// Number of segments (SST files) in L0
+    pub l0_segments: Option<u64>,
+}
+
+/// Parses the cumulative write stall time (in seconds) from the `rocksdb.stats` property
+///
+/// The line looks like `Cumulative stall: 00:00:1.234 H:M:S, 0.1 percent`.
+#[cfg(feature = "rocksdb")]
+fn rocksdb_cumulative_stall(stats: &str) -> Option<f64> {
+    let line = stats
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Cumulative stall:"))?;
+
+    let time = line.split_whitespace().next()?;
+    let mut parts = time.split(':');
+
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+
+    Some(hours * 3_600.0 + minutes * 60.0 + seconds)
+}
+
+impl GenericDatabase {
+    /// Compaction state, or `None` for backends that are not LSM-trees
+    pub fn compaction_state(&self) -> Option<CompactionState> {
+        match self {
+            #[cfg(feature = "rocksdb")]
+            GenericDatabase::RocksDb(db) => {
+                let int = |name: &str| db.property_int_value(name).ok().flatten();
+
+                let stopped = int("rocksdb.is-write-stopped").is_some_and(|x| x > 0);
+                let delayed = int("rocksdb.actual-delayed-write-rate").is_some_and(|x| x > 0);
+
+                Some(CompactionState {
+                    write_stall: stopped || delayed,
+                    cumulative_stall_secs: db
+                        .property_value("rocksdb.stats")
+                        .ok()
+                        .flatten()
+                        .and_then(|stats| rocksdb_cumulative_stall(&stats)),
+                    pending_compaction_bytes: int("rocksdb.estimate-pending-compaction-bytes"),
+                    l0_segments: db
+                        .property_value("rocksdb.num-files-at-level0")
+                        .ok()
+                        .flatten()
+                        .and_then(|x| x.trim().parse().ok()),
+                })
+            }
+
+            GenericDatabase::Fjall { db, .. } => {
+                // NOTE: fjall does not report stalls, so they are inferred from the L0 segment count
+                let l0_segments = db.tree.first_level_segment_count();
+
+                Some(CompactionState {
+                    write_stall: l0_segments > FJALL_L0_STALL_THRESHOLD,
+                    cumulative_stall_secs: None,
+                    pending_compaction_bytes: None,
+                    l0_segments: Some(l0_segments as u64),
+                })
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Engine-internal statistics, or `None` if the backend does not export any
+    pub fn backend_stats(&self) -> Option<serde_json::Value> {
+        match self {
+            #[cfg(feature = "rocksdb")]
+            GenericDatabase::RocksDb(db) => {
+                let int = |name: &str| db.property_int_value(name).ok().flatten();
+
+                let l0_files = db
+                    .property_value("rocksdb.num-files-at-level0")
+                    .ok()
+                    .flatten()
+                    .and_then(|x| x.trim().parse::<u64>().ok());
+
+                let stall_secs = db
+                    .property_value("rocksdb.stats")
+                    .ok()
+                    .flatten()
+                    .and_then(|stats| rocksdb_cumulative_stall(&stats));
+
+                Some(json!({
+                    "pending_compaction_bytes": int("rocksdb.estimate-pending-compaction-bytes"),
+                    "running_compactions": int("rocksdb.num-running-compactions"),
+                    "running_flushes": int("rocksdb.num-running-flushes"),
+                    "memtable_bytes": int("rocksdb.cur-size-all-mem-tables"),
+                    "sst_bytes": int("rocksdb.total-sst-files-size"),
+                    "delayed_write_rate": int("rocksdb.actual-delayed-write-rate"),
+                    "write_stopped": int("rocksdb.is-write-stopped").map(|x| x > 0),
+                    "l0_files": l0_files,
+                    "cumulative_stall_secs": stall_secs,
+                }))
+            }
+
+            #[cfg(feature = "heed")]
+            GenericDatabase::Heed { db, env, map } => {
+                let _lock = map.read();
+
+                let info = env.info();
+                let stat = env.read_txn().and_then(|rtxn| db.stat(&rtxn)).ok()?;
+
+                Some(json!({
+                    "map_size": info.map_size,
+                    "last_page_number": info.last_page_number,
+                    "last_txn_id": info.last_txn_id,
+                    "readers": info.number_of_readers,
+                    "max_readers": info.maximum_number_of_readers,
+                    "non_free_pages_bytes": env.non_free_pages_size().ok(),
+                    "page_size": stat.page_size,
+                    "depth": stat.depth,
+                    "branch_pages": stat.branch_pages,
+                    "leaf_pages": stat.leaf_pages,
+                    "overflow_pages": stat.overflow_pages,
+                    "entries": stat.entries,
+                }))
+            }
+
+            GenericDatabase::Fjall { keyspace, db } => Some(json!({
+                "segment_count": db.segment_count(),
+                "partition_disk_space": db.disk_space(),
+                "approximate_len": db.approximate_len(),
+                "journal_count": keyspace.journal_count(),
+                "write_buffer_size": keyspace.write_buffer_size(),
+                "disk_space": keyspace.disk_space(),
+            })),
+
+            GenericDatabase::Sled(db) => Some(json!({
+                "size_on_disk": db.size_on_disk().ok(),
+                "was_recovered": db.was_recovered(),
+            })),
+
+            GenericDatabase::Redb(db) => Some(json!({
+                "cache_evictions": db.cache_stats().evictions(),
+            })),
+
+            GenericDatabase::Jamm(_)
+            | GenericDatabase::Persy(_)
+            | GenericDatabase::Nebari { .. } => None,
+        }
+    }
+}
@@ -1,21 +1,67 @@
+use crate::backend_opts::BackendOpts;
+use crate::error::DbError;
+#[cfg(feature = "heed")]
+use crate::heed_map::HeedMap;
+use crate::shutdown;
+use crate::stats::{Stats, ThreadStats};
 use crate::Args;
 use nebari::{io::fs::StdFile, tree::Unversioned};
 use redb::TableDefinition;
+use rust_storage_bench::{Backend, ErrorPolicy, LsmCompaction};
 use std::{
-    sync::{atomic::AtomicU64, Arc},
-    time::Instant,
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering::Relaxed, Arc, OnceLock},
+    time::{Duration, Instant},
 };
 
 #[derive(Clone)]
 pub struct DatabaseWrapper {
     pub inner: GenericDatabase,
-    pub write_ops: Arc<AtomicU64>,
-    pub read_ops: Arc<AtomicU64>,
-    pub delete_ops: Arc<AtomicU64>,
-    pub scan_ops: Arc<AtomicU64>,
+    pub stats: Arc<Stats>,
 
-    pub write_latency: Arc<AtomicU64>,
-    pub read_latency: Arc<AtomicU64>,
+    /// Stat slot of the thread using this handle
+    slot: usize,
+
+    on_error: ErrorPolicy,
+    error_retries: u16,
+}
+
+/// Operation type, used to attribute errors
+#[derive(Copy, Clone)]
+enum Op {
+    Write,
+    Read,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Write => "write",
+            Self::Read => "read",
+        }
+    }
+}
+
+static FATAL_ERROR: OnceLock<String> = OnceLock::new();
+
+/// Returns the error that aborted the run (with `--on-error abort`)
+pub fn fatal_error() -> Option<&'static str> {
+    FATAL_ERROR.get().map(String::as_str)
+}
+
+/// Aborts the run, running the exit hooks, so the end-of-run records are still written
+fn abort(op: Op, err: &DbError) -> ! {
+    log::error!("{} failed, aborting: {err}", op.as_str());
+
+    if FATAL_ERROR.set(format!("{}: {err}", op.as_str())).is_ok() {
+        shutdown::exit(1);
+    }
+
+    // NOTE: Another thread is already shutting down
+    loop {
+        std::thread::park();
+    }
 }
 
 impl std::ops::Deref for DatabaseWrapper {
@@ -38,6 +84,7 @@ pub enum GenericDatabase {
     Persy(persy::Persy),
     Redb(Arc<redb::Database>),
     Nebari {
+        #[allow(dead_code)]
         roots: nebari::Roots<StdFile>,
         tree: nebari::Tree<Unversioned, StdFile>,
     },
@@ -46,95 +93,469 @@ pub enum GenericDatabase {
     Heed {
         db: heed::Database<heed::types::Bytes, heed::types::Bytes>,
         env: heed::Env,
+        map: Arc<HeedMap>,
     },
 
     #[cfg(feature = "rocksdb")]
     RocksDb(Arc<rocksdb::DB>),
 }
 
+/// Returns the data folder of the selected backend
+pub fn data_dir(args: &Args) -> PathBuf {
+    Path::new(".data").join(match args.backend {
+        Backend::Fjall => match args.lsm_compaction {
+            LsmCompaction::Leveled => "fjall_lcs".to_owned(),
+            LsmCompaction::Tiered => "fjall_stcs".to_owned(),
+            LsmCompaction::Fifo => "fjall_fifo".to_owned(),
+        },
+        be => be.to_string(),
+    })
+}
+
+/// Opens (or creates) the database of the selected backend in the given folder
+///
+/// Exits with a usage error if `--backend-opt` contains options the backend does not support.
+pub fn open(args: &Args, data_dir: &Path) -> GenericDatabase {
+    let mut backend_opts = BackendOpts::new(args);
+
+    let db = open_backend(args, data_dir, &mut backend_opts);
+
+    backend_opts.finish();
+
+    db
+}
+
+fn open_backend(args: &Args, data_dir: &Path, backend_opts: &mut BackendOpts) -> GenericDatabase {
+    match args.backend {
+        #[cfg(feature = "rocksdb")]
+        Backend::RocksDb => {
+            create_dir_all(data_dir).unwrap();
+
+            let mut opts = rocksdb::Options::default();
+            opts.set_manual_wal_flush(true);
+            opts.create_if_missing(true);
+
+            if let Some(size) = backend_opts.get("write_buffer_size") {
+                opts.set_write_buffer_size(size);
+            }
+            if let Some(n) = backend_opts.get("max_write_buffer_number") {
+                opts.set_max_write_buffer_number(n);
+            }
+            if let Some(n) = backend_opts.get("max_background_jobs") {
+                opts.set_max_background_jobs(n);
+            }
+            if let Some(size) = backend_opts.get("target_file_size_base") {
+                opts.set_target_file_size_base(size);
+            }
+            if let Some(size) = backend_opts.get("max_bytes_for_level_base") {
+                opts.set_max_bytes_for_level_base(size);
+            }
+
+            let db = rocksdb::DB::open(&opts, data_dir).unwrap();
+            GenericDatabase::RocksDb(Arc::new(db))
+        }
+
+        #[cfg(feature = "heed")]
+        Backend::Heed => {
+            create_dir_all(data_dir).unwrap();
+
+            let mut opts = heed::EnvOpenOptions::new();
+            opts.map_size(HeedMap::initial_size(args));
+
+            if let Some(n) = backend_opts.get("max_readers") {
+                opts.max_readers(n);
+            }
+
+            let env = unsafe { opts.open(data_dir).unwrap() };
+
+            let mut wtxn = env.write_txn().unwrap();
+            let db = env.create_database(&mut wtxn, None).unwrap();
+            wtxn.commit().unwrap();
+
+            GenericDatabase::Heed {
+                db,
+                env,
+                map: Arc::new(HeedMap::new(args)),
+            }
+        }
+        Backend::Fjall => {
+            use fjall::{
+                compaction::{Fifo, Levelled, SizeTiered, Strategy},
+                BlockCache, PartitionCreateOptions,
+            };
+            use std::num::NonZeroU8;
+
+            let compaction_strategy: Arc<dyn Strategy + Send + Sync> = match args.lsm_compaction {
+                LsmCompaction::Leveled => {
+                    let mut strategy = Levelled::default();
+
+                    if let Some(n) = backend_opts.get("l0_threshold") {
+                        strategy.l0_threshold = n;
+                    }
+                    if let Some(bytes) = backend_opts.get("target_size") {
+                        strategy.target_size = bytes;
+                    }
+
+                    Arc::new(strategy)
+                }
+                LsmCompaction::Tiered => match backend_opts.get("base_size") {
+                    Some(bytes) => Arc::new(SizeTiered::new(bytes)),
+                    None => Arc::new(SizeTiered::default()),
+                },
+                LsmCompaction::Fifo => Arc::new(Fifo::new(
+                    backend_opts.get("fifo_limit").unwrap_or(u64::MAX),
+                    backend_opts.get("fifo_ttl_secs"),
+                )),
+            };
+
+            let mut config = fjall::Config::new(data_dir)
+                .fsync_ms(if args.fsync { None } else { Some(1_000) })
+                .block_cache(BlockCache::with_capacity_bytes(args.cache_size.into()).into());
+
+            if let Some(bytes) = backend_opts.get("max_write_buffer_size") {
+                config = config.max_write_buffer_size(bytes);
+            }
+            if let Some(bytes) = backend_opts.get("max_journaling_size") {
+                config = config.max_journaling_size(bytes);
+            }
+            if let Some(n) = backend_opts.get("flush_workers") {
+                config = config.flush_workers(n);
+            }
+            if let Some(n) = backend_opts.get("compaction_workers") {
+                config = config.compaction_workers(n);
+            }
+
+            let mut create_opts =
+                PartitionCreateOptions::default().block_size(args.lsm_block_size.into());
+
+            if let Some(n) = backend_opts.get::<NonZeroU8>("level_ratio") {
+                create_opts = create_opts.level_ratio(n.get());
+            }
+            if let Some(n) = backend_opts.get("level_count") {
+                create_opts = create_opts.level_count(n);
+            }
+
+            let keyspace = config.open().unwrap();
+            let db = keyspace.open_partition("data", create_opts).unwrap();
+            db.set_compaction_strategy(compaction_strategy);
+
+            if let Some(bytes) = backend_opts.get("max_memtable_size") {
+                db.set_max_memtable_size(bytes);
+            }
+
+            GenericDatabase::Fjall { keyspace, db }
+        }
+        Backend::Sled => {
+            let mut config = sled::Config::new()
+                .path(data_dir)
+                .flush_every_ms(if args.fsync { None } else { Some(1_000) })
+                .cache_capacity(args.cache_size as u64);
+
+            if let Some(mode) = backend_opts.get_choice("mode", &["low_space", "high_throughput"]) {
+                config = config.mode(match mode {
+                    "low_space" => sled::Mode::LowSpace,
+                    _ => sled::Mode::HighThroughput,
+                });
+            }
+            if let Some(size) = backend_opts.get("segment_size") {
+                config = config.segment_size(size);
+            }
+            if let Some(flag) = backend_opts.get("use_compression") {
+                config = config.use_compression(flag);
+            }
+            if let Some(factor) = backend_opts.get("compression_factor") {
+                config = config.compression_factor(factor);
+            }
+
+            GenericDatabase::Sled(config.open().unwrap())
+        }
+        // Backend::Bloodstone => GenericDatabase::Bloodstone(
+        //     bloodstone::Config::new()
+        //         .cache_capacity_bytes(args.cache_size as usize)
+        //         .path(data_dir)
+        //         .open()
+        //         .unwrap(),
+        // ),
+        Backend::JammDb => {
+            create_dir_all(data_dir).unwrap();
+
+            let mut opts = jammdb::OpenOptions::new();
+
+            if let Some(size) = backend_opts.get("pagesize") {
+                opts = opts.pagesize(size);
+            }
+            if let Some(n) = backend_opts.get("num_pages") {
+                opts = opts.num_pages(n);
+            }
+
+            let db = opts.open(data_dir.join("data.db")).unwrap();
+            let tx = db.tx(true).unwrap();
+            let _ = tx.get_or_create_bucket("data").unwrap();
+            tx.commit().unwrap();
+
+            GenericDatabase::Jamm(db)
+        }
+
+        Backend::Persy => {
+            use persy::{Config, Persy, PersyId, ValueMode};
+
+            create_dir_all(data_dir).unwrap();
+
+            let mut cfg = Config::default();
+            cfg.change_cache_size(args.cache_size.into());
+
+            if let Some(ms) = backend_opts.get("transaction_lock_timeout_ms") {
+                cfg.change_transaction_lock_timeout(Duration::from_millis(ms));
+            }
+            if let Some(ms) = backend_opts.get("cache_age_limit_ms") {
+                cfg.change_cache_age_limit(Duration::from_millis(ms));
+            }
+
+            let db = Persy::open_or_create_with(data_dir.join("data.persy"), cfg, |db| {
+                let mut tx = db.begin()?;
+                tx.create_segment("data")?;
+                tx.create_index::<String, PersyId>("primary", ValueMode::Replace)?;
+                let prepared = tx.prepare()?;
+                prepared.commit()?;
+                Ok(())
+            })
+            .unwrap();
+
+            GenericDatabase::Persy(db)
+        }
+        Backend::Redb => {
+            create_dir_all(data_dir).unwrap();
+
+            GenericDatabase::Redb(Arc::new(
+                redb::Builder::new()
+                    .set_cache_size(args.cache_size as usize)
+                    .create(data_dir.join("my_db.redb"))
+                    .unwrap(),
+            ))
+        }
+        Backend::Nebari => {
+            use nebari::{
+                tree::{Root, Unversioned},
+                Config,
+            };
+
+            create_dir_all(data_dir).unwrap();
+
+            let roots = Config::default_for(data_dir.join("db.nebari"))
+                .open()
+                .unwrap();
+            let tree = roots.tree(Unversioned::tree("data")).unwrap();
+
+            GenericDatabase::Nebari { roots, tree }
+        }
+    }
+}
+
 const TABLE: TableDefinition<&[u8], Vec<u8>> = TableDefinition::new("data");
 
 impl DatabaseWrapper {
-    pub fn insert(&self, key: &[u8], value: &[u8], durable: bool, args: Arc<Args>) {
+    pub fn new(inner: GenericDatabase, workers: usize, args: &Args) -> Self {
+        Self {
+            inner,
+            stats: Arc::new(Stats::new(workers)),
+            slot: 0,
+            on_error: args.on_error,
+            error_retries: args.error_retries,
+        }
+    }
+
+    /// Returns a handle for the given worker thread, which records into its own stat slot
+    pub fn for_worker(&self, idx: usize) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            stats: self.stats.clone(),
+            slot: idx + 1,
+            on_error: self.on_error,
+            error_retries: self.error_retries,
+        }
+    }
+
+    /// Takes the LMDB map resizes that were not written to the output yet
+    #[cfg(feature = "heed")]
+    pub fn take_heed_resizes(&self) -> Vec<crate::heed_map::Resize> {
         match &self.inner {
+            GenericDatabase::Heed { map, .. } => map.take_resizes(),
+            _ => vec![],
+        }
+    }
+
+    fn thread_stats(&self) -> &ThreadStats {
+        self.stats.slot(self.slot)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub fn record_scheduling_delay(&self, delay: std::time::Duration) {
+        let delay = delay.as_micros() as u64;
+
+        let stats = self.thread_stats();
+        stats.scheduling_delay.fetch_add(delay, Relaxed);
+        stats.scheduling_histogram.record(delay);
+    }
+
+    /// Flushes and closes the database, so it can be reopened by the same process
+    pub fn close(self) {
+        match self.inner {
             #[cfg(feature = "rocksdb")]
-            GenericDatabase::RocksDb(db) => {
-                let start = Instant::now();
+            GenericDatabase::RocksDb(db) => db.flush().unwrap(),
 
-                db.put(key, value).unwrap();
+            #[cfg(feature = "heed")]
+            GenericDatabase::Heed { env, .. } => {
+                env.force_sync().unwrap();
 
-                if durable {
-                    db.flush_wal(true).unwrap();
-                }
+                // NOTE: heed keeps environments open globally until they are explicitly closed
+                env.prepare_for_closing().wait();
+            }
 
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
+            GenericDatabase::Fjall { keyspace, .. } => {
+                keyspace.persist(fjall::PersistMode::SyncAll).unwrap();
             }
+            GenericDatabase::Sled(db) => {
+                db.flush().unwrap();
+            }
+            _ => {}
+        }
+    }
 
-            #[cfg(feature = "heed")]
-            GenericDatabase::Heed { env, db } => {
-                let start = Instant::now();
+    pub fn insert(&self, key: &[u8], value: &[u8], durable: bool) -> Result<(), DbError> {
+        self.insert_at(Instant::now(), key, value, durable)
+    }
+
+    /// Inserts an item, measuring latency from the given (intended) start time
+    pub fn insert_at(
+        &self,
+        start: Instant,
+        key: &[u8],
+        value: &[u8],
+        durable: bool,
+    ) -> Result<(), DbError> {
+        self.with_policy(Op::Write, || self.try_insert(key, value, durable))?;
+
+        let latency = start.elapsed().as_micros() as u64;
+
+        let stats = self.thread_stats();
+        stats.write_latency.fetch_add(latency, Relaxed);
+        stats.write_histogram.record(latency);
+        stats.write_ops.fetch_add(1, Relaxed);
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        self.get_at(Instant::now(), key)
+    }
+
+    /// Reads an item, measuring latency from the given (intended) start time
+    pub fn get_at(&self, start: Instant, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        let item = self.with_policy(Op::Read, || self.try_get(key))?;
 
-                let mut wtxn = env.write_txn().unwrap();
-                db.put(&mut wtxn, key, value).unwrap();
+        let latency = start.elapsed().as_micros() as u64;
 
-                wtxn.commit().unwrap();
+        let stats = self.thread_stats();
+        stats.read_latency.fetch_add(latency, Relaxed);
+        stats.read_histogram.record(latency);
+        stats.read_ops.fetch_add(1, Relaxed);
 
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
+        Ok(item)
+    }
+
+    /// Runs an operation, applying the error policy selected by `--on-error`
+    ///
+    /// Every failed attempt is counted; only successful operations are counted as ops.
+    fn with_policy<T>(
+        &self,
+        op: Op,
+        mut f: impl FnMut() -> Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        let stats = self.thread_stats();
+        let mut retries = 0;
+
+        loop {
+            let err = match f() {
+                Ok(item) => return Ok(item),
+                Err(err) => err,
+            };
+
+            match op {
+                Op::Write => stats.write_errors.record(err.kind),
+                Op::Read => stats.read_errors.record(err.kind),
+            }
+
+            match self.on_error {
+                ErrorPolicy::Abort => abort(op, &err),
+                ErrorPolicy::Retry if retries < self.error_retries => {
+                    log::debug!("{} failed, retrying: {err}", op.as_str());
+
+                    retries += 1;
+                    stats.retries.fetch_add(1, Relaxed);
+                }
+                _ => {
+                    log::debug!("{} failed: {err}", op.as_str());
+                    return Err(err);
+                }
             }
+        }
+    }
+
+    fn try_insert(&self, key: &[u8], value: &[u8], durable: bool) -> Result<(), DbError> {
+        match &self.inner {
+            #[cfg(feature = "rocksdb")]
+            GenericDatabase::RocksDb(db) => {
+                db.put(key, value)?;
+
+                if durable {
+                    db.flush_wal(true)?;
+                }
+            }
+
+            #[cfg(feature = "heed")]
+            GenericDatabase::Heed { env, db, map } => loop {
+                let lock = map.read();
+                let seen = env.info().map_size;
+
+                let result = env.write_txn().and_then(|mut wtxn| {
+                    db.put(&mut wtxn, key, value)?;
+                    wtxn.commit()
+                });
+
+                drop(lock);
+
+                match result {
+                    Err(heed::Error::Mdb(heed::MdbError::MapFull)) if map.auto_grow() => {
+                        map.grow(env, seen)?;
+                    }
+                    result => break result?,
+                }
+            },
             GenericDatabase::Nebari { roots: _, tree } => {
                 if !durable {
                     log::warn!("WARNING: Nebari does not support eventual durability");
                 }
 
                 let key = key.to_vec();
-                let value = key.to_vec();
-
-                let start = Instant::now();
-
-                tree.set(key, value).unwrap();
+                let value = value.to_vec();
 
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
+                tree.set(key, value)?;
             }
             GenericDatabase::Fjall { keyspace, db } => {
-                let start = Instant::now();
-
-                db.insert(key, value).unwrap();
+                db.insert(key, value)?;
 
                 if durable {
-                    keyspace.persist(fjall::FlushMode::SyncAll).unwrap();
+                    keyspace.persist(fjall::PersistMode::SyncAll)?;
                 }
-
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
             }
             GenericDatabase::Sled(db) => {
-                let start = Instant::now();
-
-                db.insert(key, value).unwrap();
+                db.insert(key, value)?;
 
                 if durable {
-                    db.flush().unwrap();
+                    db.flush()?;
                 }
-
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
             }
             // GenericDatabase::Bloodstone(db) => {
-            //     let start = Instant::now();
-
             //     db.insert(key, value).unwrap();
 
             //     if durable {
@@ -143,32 +564,20 @@ impl DatabaseWrapper {
             //         // NOTE: TODO: OOM Workaround
             //         // Intermittenly flush sled to keep memory usage sane
             //         // This is hopefully a temporary workaround
-            //         if self.write_ops.load(std::sync::atomic::Ordering::Relaxed) % 5_000_000 == 0 {
+            //         if self.thread_stats().write_ops.load(Relaxed) % 5_000_000 == 0 {
             //             db.flush().unwrap();
             //         }
             //     }
-
-            //     self.write_latency.fetch_add(
-            //         start.elapsed().as_micros() as u64,
-            //         std::sync::atomic::Ordering::Relaxed,
-            //     );
             // }
             GenericDatabase::Jamm(db) => {
                 if !durable {
                     log::warn!("WARNING: JammDB does not support eventual durability",);
                 }
 
-                let start = Instant::now();
-
-                let tx = db.tx(true).unwrap();
-                let bucket = tx.get_bucket("data").unwrap();
-                bucket.put(key, value).unwrap();
-                tx.commit().unwrap();
-
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
+                let tx = db.tx(true)?;
+                let bucket = tx.get_bucket("data")?;
+                bucket.put(key, value)?;
+                tx.commit()?;
             }
             GenericDatabase::Persy(db) => {
                 use persy::{PersyId, TransactionConfig};
@@ -176,102 +585,80 @@ impl DatabaseWrapper {
                 let key = String::from_utf8_lossy(key);
                 let key = key.to_string();
 
-                let start = Instant::now();
-
-                let mut tx = db
-                    .begin_with(TransactionConfig::new().set_background_sync(!durable))
-                    .unwrap();
-                let id = tx.insert("data", value).unwrap();
-
-                tx.put::<String, PersyId>("primary", key, id).unwrap();
-                let prepared = tx.prepare().unwrap();
+                let mut tx =
+                    db.begin_with(TransactionConfig::new().set_background_sync(!durable))?;
+                let id = tx.insert("data", value)?;
 
-                prepared.commit().unwrap();
+                tx.put::<String, PersyId>("primary", key, id)?;
+                let prepared = tx.prepare()?;
 
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
+                prepared.commit()?;
             }
             GenericDatabase::Redb(db) => {
                 use redb::Durability::{Eventual, Immediate};
 
-                let start = Instant::now();
-
-                let mut write_txn = db.begin_write().unwrap();
+                // NOTE: Every redb operation has its own error type, which all convert into redb::Error
+                let mut write_txn = db.begin_write().map_err(redb::Error::from)?;
 
                 write_txn.set_durability(if durable { Immediate } else { Eventual });
 
                 {
-                    let mut table = write_txn.open_table(TABLE).unwrap();
-                    table.insert(key, value.to_vec()).unwrap();
+                    let mut table = write_txn.open_table(TABLE).map_err(redb::Error::from)?;
+                    table
+                        .insert(key, value.to_vec())
+                        .map_err(redb::Error::from)?;
                 }
-                write_txn.commit().unwrap();
-
-                self.write_latency.fetch_add(
-                    start.elapsed().as_micros() as u64,
-                    std::sync::atomic::Ordering::Relaxed,
-                );
+                write_txn.commit().map_err(redb::Error::from)?;
             }
         }
 
-        self.write_ops
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
     }
 
-    pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
-        let start = Instant::now();
-
+    fn try_get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
         let item = match &self.inner {
             #[cfg(feature = "rocksdb")]
-            GenericDatabase::RocksDb(db) => db.get(key).unwrap().map(|x| x.to_vec()),
+            GenericDatabase::RocksDb(db) => db.get(key)?.map(|x| x.to_vec()),
 
             #[cfg(feature = "heed")]
-            GenericDatabase::Heed { db, env } => {
-                let rtxn = env.read_txn().unwrap();
-                let ret = db.get(&rtxn, key).unwrap();
+            GenericDatabase::Heed { db, env, map } => {
+                let _lock = map.read();
+
+                let rtxn = env.read_txn()?;
+                let ret = db.get(&rtxn, key)?;
                 ret.map(|x| x.to_vec())
             }
 
             GenericDatabase::Nebari { roots: _, tree } => {
-                let item = tree.get(key).unwrap();
+                let item = tree.get(key)?;
                 item.map(|x| x.to_vec())
             }
-            GenericDatabase::Fjall { keyspace: _, db } => db.get(key).unwrap().map(|x| x.to_vec()),
-            GenericDatabase::Sled(db) => db.get(key).unwrap().map(|x| x.to_vec()),
+            GenericDatabase::Fjall { keyspace: _, db } => db.get(key)?.map(|x| x.to_vec()),
+            GenericDatabase::Sled(db) => db.get(key)?.map(|x| x.to_vec()),
             // GenericDatabase::Bloodstone(db) => db.get(key).unwrap().map(|x| x.to_vec()),
             GenericDatabase::Jamm(db) => {
-                let tx = db.tx(false).unwrap();
-                let bucket = tx.get_bucket("data").unwrap();
+                let tx = db.tx(false)?;
+                let bucket = tx.get_bucket("data")?;
                 bucket.get(key).map(|item| item.kv().value().into())
             }
             GenericDatabase::Persy(db) => {
                 let key = String::from_utf8_lossy(key);
 
-                let mut read_id = db
-                    .get::<String, persy::PersyId>("primary", &key.to_string())
-                    .unwrap();
+                let mut read_id = db.get::<String, persy::PersyId>("primary", &key.to_string())?;
                 if let Some(id) = read_id.next() {
-                    db.read("data", &id).unwrap()
+                    db.read("data", &id)?
                 } else {
                     None
                 }
             }
             GenericDatabase::Redb(db) => {
-                let read_txn = db.begin_read().unwrap();
-                let table = read_txn.open_table(TABLE).unwrap();
-                table.get(key).unwrap().map(|x| x.value())
+                let read_txn = db.begin_read().map_err(redb::Error::from)?;
+                let table = read_txn.open_table(TABLE).map_err(redb::Error::from)?;
+                let item = table.get(key).map_err(redb::Error::from)?;
+                item.map(|x| x.value())
             }
         };
 
-        self.read_latency.fetch_add(
-            start.elapsed().as_micros() as u64,
-            std::sync::atomic::Ordering::Relaxed,
-        );
-
-        self.read_ops
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-        item
+        Ok(item)
     }
 }
@@ -0,0 +1,220 @@
+use serde::Serialize;
+use std::error::Error;
+
+/// Classification of a failed storage engine operation
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The memory map is full (LMDB's fixed `map_size`)
+    MapFull,
+
+    /// The disk (or quota) is full
+    DiskFull,
+
+    /// The operation conflicted with a concurrent operation and may succeed when retried
+    Conflict,
+
+    /// The storage engine detected corrupted data
+    Corruption,
+
+    /// Any other I/O error
+    Io,
+
+    /// Anything else
+    Other,
+}
+
+impl ErrorKind {
+    pub const ALL: [Self; 6] = [
+        Self::MapFull,
+        Self::DiskFull,
+        Self::Conflict,
+        Self::Corruption,
+        Self::Io,
+        Self::Other,
+    ];
+
+    /// Index into [`ErrorKind::ALL`]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Error of a storage engine operation
+#[derive(Debug)]
+pub struct DbError {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+// NOTE: DbError does not implement Error itself, otherwise this would conflict with `From<T> for T`
+impl<E: Error + 'static> From<E> for DbError {
+    fn from(err: E) -> Self {
+        Self {
+            kind: classify(&err),
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Classifies an error by walking its source chain
+///
+/// Most storage engines do not expose their I/O errors through `source`,
+/// so their error types are unpacked explicitly.
+fn classify(err: &(dyn Error + 'static)) -> ErrorKind {
+    let mut next = Some(err);
+
+    while let Some(err) = next {
+        if let Some(kind) = classify_one(err) {
+            return kind;
+        }
+
+        next = err.source();
+    }
+
+    ErrorKind::Other
+}
+
+fn classify_io(err: &std::io::Error) -> ErrorKind {
+    match err.kind() {
+        std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded => ErrorKind::DiskFull,
+        _ => ErrorKind::Io,
+    }
+}
+
+fn classify_persy_generic(err: &persy::GenericError) -> ErrorKind {
+    match err {
+        persy::GenericError::Io { from } => classify_io(from),
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Persy wraps every operation's error in its own type, none of which expose their I/O error through `source`
+fn classify_persy(err: &(dyn Error + 'static)) -> Option<ErrorKind> {
+    use persy::PE;
+
+    if let Some(PE::PE(err)) = err.downcast_ref::<PE<persy::GenericError>>() {
+        return Some(classify_persy_generic(err));
+    }
+
+    if let Some(PE::PE(err)) = err.downcast_ref::<PE<persy::PrepareError>>() {
+        use persy::PrepareError;
+
+        return Some(match err {
+            PrepareError::Generic(err) => classify_persy_generic(err),
+            PrepareError::VersionNotLatest | PrepareError::ReachedLimitOfRetry => {
+                ErrorKind::Conflict
+            }
+            _ => ErrorKind::Other,
+        });
+    }
+
+    // NOTE: The remaining error types only differ in their non-I/O variants
+    macro_rules! generic {
+        ($($ty:ident),*) => {
+            $(
+                if let Some(PE::PE(err)) = err.downcast_ref::<PE<persy::$ty>>() {
+                    return Some(match err {
+                        persy::$ty::Generic(err) => classify_persy_generic(err),
+                        _ => ErrorKind::Other,
+                    });
+                }
+            )*
+        };
+    }
+
+    generic!(
+        BeginTransactionError,
+        InsertError,
+        IndexPutError,
+        IndexOpsError,
+        IndexChangeError,
+        ReadError
+    );
+
+    None
+}
+
+fn classify_one(err: &(dyn Error + 'static)) -> Option<ErrorKind> {
+    if let Some(err) = err.downcast_ref::<std::io::Error>() {
+        return Some(classify_io(err));
+    }
+
+    #[cfg(feature = "heed")]
+    if let Some(err) = err.downcast_ref::<heed::Error>() {
+        use heed::MdbError;
+
+        return Some(match err {
+            heed::Error::Io(err) => classify_io(err),
+            heed::Error::Mdb(MdbError::MapFull) => ErrorKind::MapFull,
+            heed::Error::Mdb(MdbError::Corrupted | MdbError::PageNotFound) => ErrorKind::Corruption,
+            _ => ErrorKind::Other,
+        });
+    }
+
+    #[cfg(feature = "rocksdb")]
+    if let Some(err) = err.downcast_ref::<rocksdb::Error>() {
+        use rocksdb::ErrorKind as Rocks;
+
+        return Some(match err.kind() {
+            Rocks::IOError if err.to_string().contains("No space left") => ErrorKind::DiskFull,
+            Rocks::IOError => ErrorKind::Io,
+            Rocks::Corruption => ErrorKind::Corruption,
+            Rocks::Busy | Rocks::TryAgain => ErrorKind::Conflict,
+            _ => ErrorKind::Other,
+        });
+    }
+
+    if let Some(err) = err.downcast_ref::<fjall::Error>() {
+        return Some(match err {
+            fjall::Error::Io(err) | fjall::Error::Storage(fjall::LsmError::Io(err)) => {
+                classify_io(err)
+            }
+            _ => ErrorKind::Other,
+        });
+    }
+
+    if let Some(err) = err.downcast_ref::<sled::Error>() {
+        return Some(match err {
+            sled::Error::Io(err) => classify_io(err),
+            sled::Error::Corruption { .. } => ErrorKind::Corruption,
+            _ => ErrorKind::Other,
+        });
+    }
+
+    if let Some(err) = err.downcast_ref::<jammdb::Error>() {
+        return Some(match err {
+            jammdb::Error::Io(err) => classify_io(err),
+            jammdb::Error::InvalidDB(_) => ErrorKind::Corruption,
+            _ => ErrorKind::Other,
+        });
+    }
+
+    if let Some(kind) = classify_persy(err) {
+        return Some(kind);
+    }
+
+    if let Some(err) = err.downcast_ref::<redb::Error>() {
+        return Some(match err {
+            redb::Error::Io(err) => classify_io(err),
+            redb::Error::Corrupted(_) => ErrorKind::Corruption,
+            _ => ErrorKind::Other,
+        });
+    }
+
+    if let Some(err) = err.downcast_ref::<nebari::Error>() {
+        return Some(match &err.kind {
+            nebari::ErrorKind::Io(err) => classify_io(err),
+            nebari::ErrorKind::DataIntegrity(_) => ErrorKind::Corruption,
+            _ => ErrorKind::Other,
+        });
+    }
+
+    None
+}
@@ -0,0 +1,185 @@
+//! Global allocator that counts allocations, used for `--snapshot-heap`
+//!
+//! Forwards to the system allocator and tracks live bytes per size class,
+//! so heap growth can be attributed to small (e.g. keys, index nodes) or large (e.g. blocks, memtables) allocations.
+
+use rust_storage_bench::record::{HeapMetrics, SizeClassUsage};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+};
+
+/// Allocation sizes are grouped by powers of two
+const SIZE_CLASSES: usize = 64;
+
+struct SizeClass {
+    /// Allocations made so far
+    allocations: AtomicU64,
+
+    live_count: AtomicU64,
+    live_bytes: AtomicU64,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const SIZE_CLASS: SizeClass = SizeClass {
+    allocations: AtomicU64::new(0),
+    live_count: AtomicU64::new(0),
+    live_bytes: AtomicU64::new(0),
+};
+
+pub struct CountingAllocator {
+    allocations: AtomicU64,
+    deallocations: AtomicU64,
+    reallocations: AtomicU64,
+
+    /// Bytes allocated so far (including freed ones)
+    allocated_total: AtomicU64,
+
+    live_bytes: AtomicU64,
+    peak_bytes: AtomicU64,
+
+    classes: [SizeClass; SIZE_CLASSES],
+}
+
+impl CountingAllocator {
+    pub const fn new() -> Self {
+        Self {
+            allocations: AtomicU64::new(0),
+            deallocations: AtomicU64::new(0),
+            reallocations: AtomicU64::new(0),
+            allocated_total: AtomicU64::new(0),
+            live_bytes: AtomicU64::new(0),
+            peak_bytes: AtomicU64::new(0),
+            classes: [SIZE_CLASS; SIZE_CLASSES],
+        }
+    }
+
+    fn class(&self, size: usize) -> &SizeClass {
+        let idx = (usize::BITS - size.saturating_sub(1).leading_zeros()) as usize;
+        &self.classes[idx.min(SIZE_CLASSES - 1)]
+    }
+
+    fn on_alloc(&self, size: usize) {
+        let size = size as u64;
+
+        self.allocations.fetch_add(1, Relaxed);
+        self.allocated_total.fetch_add(size, Relaxed);
+
+        let live = self.live_bytes.fetch_add(size, Relaxed) + size;
+        self.peak_bytes.fetch_max(live, Relaxed);
+
+        let class = self.class(size as usize);
+        class.allocations.fetch_add(1, Relaxed);
+        class.live_count.fetch_add(1, Relaxed);
+        class.live_bytes.fetch_add(size, Relaxed);
+    }
+
+    fn on_dealloc(&self, size: usize) {
+        self.deallocations.fetch_add(1, Relaxed);
+        self.live_bytes.fetch_sub(size as u64, Relaxed);
+
+        let class = self.class(size);
+        class.live_count.fetch_sub(1, Relaxed);
+        class.live_bytes.fetch_sub(size as u64, Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+
+        if !ptr.is_null() {
+            self.on_alloc(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+
+        if !ptr.is_null() {
+            self.on_alloc(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.on_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+
+        if !new_ptr.is_null() {
+            self.reallocations.fetch_add(1, Relaxed);
+            self.on_dealloc(layout.size());
+            self.on_alloc(new_size);
+        }
+
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator::new();
+
+/// Cumulative allocator counters
+#[derive(Clone, Copy, Default)]
+pub struct HeapStats {
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub reallocations: u64,
+    pub allocated_total: u64,
+    pub live_bytes: u64,
+    pub peak_bytes: u64,
+}
+
+pub fn stats() -> HeapStats {
+    HeapStats {
+        allocations: GLOBAL.allocations.load(Relaxed),
+        deallocations: GLOBAL.deallocations.load(Relaxed),
+        reallocations: GLOBAL.reallocations.load(Relaxed),
+        allocated_total: GLOBAL.allocated_total.load(Relaxed),
+        live_bytes: GLOBAL.live_bytes.load(Relaxed),
+        peak_bytes: GLOBAL.peak_bytes.load(Relaxed),
+    }
+}
+
+impl HeapStats {
+    /// Counters for the metrics record, with rates since `prev`
+    pub fn to_metrics(self, prev: &Self, elapsed_secs: f64) -> HeapMetrics {
+        let allocations = self.allocations - prev.allocations;
+        let allocated_bytes = self.allocated_total - prev.allocated_total;
+
+        HeapMetrics {
+            live_bytes: self.live_bytes,
+            peak_bytes: self.peak_bytes,
+            live_allocations: self.allocations - self.deallocations,
+            allocations: self.allocations,
+            deallocations: self.deallocations,
+            reallocations: self.reallocations,
+            allocated_total_bytes: self.allocated_total,
+            allocations_per_sec: allocations as f64 / elapsed_secs,
+            allocated_bytes_per_sec: allocated_bytes as f64 / elapsed_secs,
+        }
+    }
+}
+
+/// Live heap per allocation size class (classes without any allocations are left out)
+pub fn profile() -> Vec<SizeClassUsage> {
+    GLOBAL
+        .classes
+        .iter()
+        .enumerate()
+        .filter(|(_, class)| class.allocations.load(Relaxed) > 0)
+        .map(|(idx, class)| SizeClassUsage {
+            max_size: 1_u64 << idx,
+            allocations: class.allocations.load(Relaxed),
+            live_count: class.live_count.load(Relaxed),
+            live_bytes: class.live_bytes.load(Relaxed),
+        })
+        .collect()
+}
@@ -0,0 +1,96 @@
+use crate::unix_timestamp;
+use rust_storage_bench::procfs::page_size;
+use rust_storage_bench::Args;
+use std::{
+    sync::{Mutex, RwLock, RwLockReadGuard},
+    time::Instant,
+};
+
+/// A resize of the LMDB memory map
+pub struct Resize {
+    pub time_micro: u128,
+    pub from_bytes: usize,
+    pub to_bytes: usize,
+
+    /// Time from hitting `MapFull` until the map was resized,
+    /// including waiting for all active transactions to finish
+    pub stall_micros: u128,
+}
+
+/// Size of the LMDB memory map
+///
+/// LMDB only allows resizing the map while no transaction is active (in this process),
+/// so with auto-grow every transaction holds the lock shared, and resizing holds it exclusively.
+pub struct HeedMap {
+    lock: RwLock<()>,
+    auto_grow: bool,
+
+    /// Resizes that were not written to the output yet
+    resizes: Mutex<Vec<Resize>>,
+}
+
+impl HeedMap {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            lock: RwLock::default(),
+            auto_grow: args.heed_auto_grow,
+            resizes: Mutex::default(),
+        }
+    }
+
+    /// Initial map size from `--heed-map-size`, rounded up to the page size
+    pub fn initial_size(args: &Args) -> usize {
+        (args.heed_map_size as usize).next_multiple_of(page_size() as usize)
+    }
+
+    pub fn auto_grow(&self) -> bool {
+        self.auto_grow
+    }
+
+    /// Needs to be held while a transaction is active
+    ///
+    /// Without `--heed-auto-grow` the map is never resized, so no lock is taken
+    /// and the hot path stays the same as plain LMDB.
+    pub fn read(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        self.auto_grow
+            .then(|| self.lock.read().expect("lock is poisoned"))
+    }
+
+    /// Doubles the map size after a transaction failed with `MapFull`
+    ///
+    /// `seen` is the map size the transaction ran with;
+    /// if another thread has grown the map in the meantime, it is not grown again.
+    pub fn grow(&self, env: &heed::Env, seen: usize) -> heed::Result<()> {
+        let start = Instant::now();
+        let _lock = self.lock.write().expect("lock is poisoned");
+
+        let from_bytes = env.info().map_size;
+
+        if from_bytes > seen {
+            return Ok(());
+        }
+
+        let to_bytes = from_bytes * 2;
+
+        // SAFETY: No transaction is active, because we hold the lock exclusively
+        unsafe { env.resize(to_bytes)? };
+
+        let stall_micros = start.elapsed().as_micros();
+
+        log::info!("heed: resized map from {from_bytes} to {to_bytes} bytes in {stall_micros}µs");
+
+        self.resizes.lock().expect("lock is poisoned").push(Resize {
+            time_micro: unix_timestamp().as_micros(),
+            from_bytes,
+            to_bytes,
+            stall_micros,
+        });
+
+        Ok(())
+    }
+
+    /// Takes all resizes that were not written to the output yet
+    pub fn take_resizes(&self) -> Vec<Resize> {
+        std::mem::take(&mut *self.resizes.lock().expect("lock is poisoned"))
+    }
+}
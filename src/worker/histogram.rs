@@ -0,0 +1,138 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering::Relaxed},
+    OnceLock,
+};
+
+/// Number of linear sub-buckets per power of two (as bits)
+///
+/// 16 sub-buckets bound the relative error of a recorded value to ~6%
+const SUB_BUCKET_BITS: u32 = 4;
+
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+const BUCKET_COUNT: usize = ((64 - SUB_BUCKET_BITS + 1) as usize) << SUB_BUCKET_BITS;
+
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+
+    let exp = 63 - value.leading_zeros();
+    let shift = exp - SUB_BUCKET_BITS;
+    let sub = (value >> shift) & (SUB_BUCKETS - 1);
+
+    (((shift + 1) as usize) << SUB_BUCKET_BITS) + sub as usize
+}
+
+/// Returns the highest value that maps into the given bucket
+fn bucket_upper_bound(idx: usize) -> u64 {
+    if (idx as u64) < SUB_BUCKETS {
+        return idx as u64;
+    }
+
+    let shift = (idx >> SUB_BUCKET_BITS) as u32 - 1;
+    let sub = idx as u64 & (SUB_BUCKETS - 1);
+    let lower = (SUB_BUCKETS + sub) << shift;
+
+    lower + ((1 << shift) - 1)
+}
+
+/// Lock-free, log-linear latency histogram (values in microseconds)
+///
+/// Recording is a single relaxed atomic increment, so it can be shared between threads.
+/// Readers take cumulative snapshots and diff them, so multiple readers
+/// (e.g. metrics thread and ramp controller) can observe the same histogram
+/// over different windows without resetting it.
+///
+/// The buckets (~7.6 KiB) are allocated on the first recorded value,
+/// so histograms that are never recorded into (e.g. write latencies of reader threads) cost nothing.
+#[derive(Default)]
+pub struct Histogram {
+    buckets: OnceLock<Box<[AtomicU64]>>,
+}
+
+impl Histogram {
+    pub fn record(&self, value: u64) {
+        let buckets = self
+            .buckets
+            .get_or_init(|| (0..BUCKET_COUNT).map(|_| AtomicU64::default()).collect());
+
+        buckets[bucket_index(value)].fetch_add(1, Relaxed);
+    }
+
+    /// Adds the current counts to the given snapshot, without copying the histogram first
+    pub fn merge_into(&self, snapshot: &mut Snapshot) {
+        let Some(buckets) = self.buckets.get() else {
+            return;
+        };
+
+        for (a, b) in snapshot.0.iter_mut().zip(buckets.iter()) {
+            *a += b.load(Relaxed);
+        }
+    }
+}
+
+/// Point-in-time copy of a [`Histogram`]'s bucket counts
+#[derive(Clone)]
+pub struct Snapshot(Vec<u64>);
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self(vec![0; BUCKET_COUNT])
+    }
+}
+
+impl Snapshot {
+    /// Returns the values recorded between `earlier` and `self`
+    pub fn delta(&self, earlier: &Self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .zip(&earlier.0)
+                .map(|(now, then)| now.saturating_sub(*then))
+                .collect(),
+        )
+    }
+
+    /// Adds the counts of another snapshot
+    pub fn merge(&mut self, other: &Self) {
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            *a += b;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// Returns the value at the given quantile (0.0 - 1.0), or 0 if empty
+    pub fn quantile(&self, q: f64) -> u64 {
+        let count = self.count();
+
+        if count == 0 {
+            return 0;
+        }
+
+        let rank = ((count as f64 * q).ceil() as u64).clamp(1, count);
+
+        let mut seen = 0;
+
+        for (idx, n) in self.0.iter().enumerate() {
+            seen += n;
+
+            if seen >= rank {
+                return bucket_upper_bound(idx);
+            }
+        }
+
+        unreachable!("rank should be within total count")
+    }
+
+    pub fn max(&self) -> u64 {
+        self.0
+            .iter()
+            .rposition(|&n| n > 0)
+            .map(bucket_upper_bound)
+            .unwrap_or_default()
+    }
+}
@@ -1,18 +1,50 @@
+mod backend_opts;
+mod backend_stats;
 mod db;
+mod error;
+#[cfg(feature = "heap-stats")]
+mod heap;
+#[cfg(feature = "heed")]
+mod heed_map;
+mod histogram;
+mod memory_limit;
+mod open_bench;
+mod output;
+mod pacer;
+mod provenance;
+mod ramp;
+mod recovery;
+mod shutdown;
+mod stalls;
+mod stats;
+#[cfg(all(feature = "sync-trace", target_os = "linux"))]
+mod sync_trace;
+mod threads;
+mod workload;
 
 use crate::db::DatabaseWrapper;
-use clap::Parser;
-use db::GenericDatabase;
-use rand::distributions::Distribution;
-use rand::Rng;
-use rust_storage_bench::{Args, Backend, Workload};
-use std::fs::{create_dir_all, remove_dir_all};
-use std::io::Write;
+use clap::{CommandFactory, Parser};
+use output::Output;
+use pacer::TargetRate;
+use recovery::AckLog;
+use rust_storage_bench::{
+    disk_usage::DiskUsage,
+    procfs,
+    record::{
+        DiskUsageRecord, ErrorMetrics, ErrorsRecord, MetricsRecord, Record, SetupRecord,
+        SystemRecord, WorkerMetrics, SCHEMA_VERSION,
+    },
+    Args, Backend, Driver, Workload,
+};
+use stalls::StallTracker;
+use stats::Counters;
+use std::fs::remove_dir_all;
 use std::path::Path;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use sysinfo::Pid;
-use zipf::ZipfDistribution;
+use threads::{ThreadTracker, THREAD_PREFIX};
+use workload::Plan;
 
 /// Gets the unix timestamp as a duration
 pub fn unix_timestamp() -> std::time::Duration {
@@ -24,21 +56,92 @@ pub fn unix_timestamp() -> std::time::Duration {
         .unwrap()
 }
 
-fn start_killer(min: u64) {
-    std::thread::spawn(move || {
-        std::thread::sleep(Duration::from_secs(min * 60));
-        std::process::exit(0);
-    });
+/// Writes a record for every LMDB map resize since the last call
+#[cfg(feature = "heed")]
+fn write_heed_resizes(db: &DatabaseWrapper, output: &Output, backend: &str) {
+    use rust_storage_bench::record::HeedResizeRecord;
+
+    for resize in db.take_heed_resizes() {
+        output.write(&Record::HeedResize(Box::new(HeedResizeRecord {
+            schema_version: SCHEMA_VERSION,
+            time_micro: resize.time_micro as u64,
+            backend: backend.to_owned(),
+            from_bytes: resize.from_bytes as u64,
+            to_bytes: resize.to_bytes as u64,
+            stall_micros: resize.stall_micros as u64,
+        })));
+    }
+}
+
+/// Shortest interval between metrics records, so the metrics thread does not busy-spin
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Longest interval between metrics records
+const MAX_SAMPLE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Interval between metrics records, from `--sample-interval` or `--samples`
+fn sample_interval(args: &Args) -> Duration {
+    match args.sample_interval {
+        Some(secs) => Duration::from_secs_f64(secs),
+        None => Duration::from_secs(u64::from(args.minutes) * 60) / args.samples.max(1),
+    }
 }
 
-/*
-#[cfg(not(target_env = "msvc"))]
-use jemallocator::Jemalloc;
+/// Writes the system and setup records that start every result file
+fn write_setup(output: &Output, args: &Args, backend: &str, data_dir: &Path) {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_cpu();
+    sys.refresh_memory();
+
+    output.write(&Record::System(Box::new(SystemRecord {
+        schema_version: SCHEMA_VERSION,
+        time_micro: unix_timestamp().as_micros() as u64,
+        os: sysinfo::System::long_os_version(),
+        kernel: sysinfo::System::kernel_version(),
+        cpu: sys.global_cpu_info().brand().to_owned(),
+        mem: sys.total_memory(),
+    })));
+
+    output.write(&Record::Setup(Box::new(SetupRecord {
+        schema_version: SCHEMA_VERSION,
+        time_micro: unix_timestamp().as_micros() as u64,
+        backend: backend.to_owned(),
+        workload: args.workload,
+        threads: args.threads,
+        items: args.items,
+        value_size: args.value_size,
+        cache_size_in_bytes: args.cache_size,
+        readers: args.readers,
+        writers: args.writers,
+        shared_keyspace: args.shared_keyspace,
+        driver: args.driver,
+        lsm_compaction: Some(args.lsm_compaction),
+        lsm_block_size: Some(args.lsm_block_size),
+        backend_opts: args.backend_opts.iter().cloned().collect(),
+        ops_per_sec: args.ops_per_sec,
+        arrival: args.arrival,
+        ramp: args.ramp,
+        backend_stats: args.backend_stats,
+        on_error: args.on_error,
+        snapshot_heap: args.snapshot_heap,
+        memory_limit: args.memory_limit,
+        sample_interval_secs: Some(sample_interval(args).as_secs_f64()),
+        sync_trace: cfg!(all(feature = "sync-trace", target_os = "linux")),
+        config: Some(serde_json::to_value(args).expect("should serialize")),
+        build: Some(provenance::build_info()),
+        filesystem: provenance::filesystem(data_dir),
+    })));
+}
 
-#[cfg(not(target_env = "msvc"))]
-#[global_allocator]
-static GLOBAL: Jemalloc = Jemalloc;
-*/
+fn start_killer(min: u64) {
+    std::thread::Builder::new()
+        .name(format!("{THREAD_PREFIX}killer"))
+        .spawn(move || {
+            std::thread::sleep(Duration::from_secs(min * 60));
+            shutdown::exit(0);
+        })
+        .expect("failed to spawn killer thread");
+}
 
 fn main() {
     env_logger::Builder::from_default_env().init();
@@ -47,724 +150,493 @@ fn main() {
 
     eprintln!("Workload: {:?}", args.workload);
     eprintln!("Backend : {:?}", args.backend);
-    eprintln!("Threads : {}", args.threads);
-    eprintln!("# items : {}", args.items);
-
-    if args.workload != Workload::TaskC {
-        if args.fsync && (args.backend == Backend::Sled/*|| args.backend == Backend::Bloodstone*/) {
-            panic!("Sled doesn't fsync...");
-        }
+    if args.readers.is_some() || args.writers.is_some() {
+        eprintln!("Readers : {}", args.readers.unwrap_or_default());
+        eprintln!("Writers : {}", args.writers.unwrap_or_default());
+    } else {
+        eprintln!("Threads : {}", args.threads);
     }
+    eprintln!("# items : {}", args.items);
 
-    let data_dir = Path::new(".data").join(match args.backend {
-        Backend::Fjall => match args.lsm_compaction {
-            rust_storage_bench::LsmCompaction::Leveled => "fjall_lcs".to_owned(),
-            rust_storage_bench::LsmCompaction::Tiered => "fjall_stcs".to_owned(),
-        },
-        be => be.to_string(),
-    });
+    let in_range = |secs: f64| {
+        (MIN_SAMPLE_INTERVAL.as_secs_f64()..=MAX_SAMPLE_INTERVAL.as_secs_f64()).contains(&secs)
+    };
 
-    if data_dir.exists() {
-        remove_dir_all(&data_dir).unwrap();
+    if args.sample_interval.is_some_and(|secs| !in_range(secs)) {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                "--sample-interval has to be between 0.001 and 3600 seconds",
+            )
+            .exit();
     }
 
-    let db = match args.backend {
-        #[cfg(feature = "rocksdb")]
-        Backend::RocksDb => {
-            create_dir_all(&data_dir).unwrap();
+    if !in_range(sample_interval(&args).as_secs_f64()) {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                "--samples results in a sample interval outside of 0.001 to 3600 seconds",
+            )
+            .exit();
+    }
 
-            let mut opts = rocksdb::Options::default();
-            opts.set_manual_wal_flush(true);
-            opts.create_if_missing(true);
+    if args.snapshot_heap && !cfg!(feature = "heap-stats") {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--snapshot-heap requires building with `--features heap-stats`",
+            )
+            .exit();
+    }
 
-            let db = rocksdb::DB::open(&opts, &data_dir).unwrap();
-            GenericDatabase::RocksDb(Arc::new(db))
+    let workers = match (args.readers, args.writers) {
+        (None, None) => u32::from(args.threads),
+        (readers, writers) => {
+            u32::from(readers.unwrap_or_default()) + u32::from(writers.unwrap_or_default())
         }
+    };
 
-        #[cfg(feature = "heed")]
-        Backend::Heed => {
-            create_dir_all(&data_dir).unwrap();
-
-            let env = unsafe {
-                heed::EnvOpenOptions::new()
-                    .map_size(8_000_000_000)
-                    .open(&data_dir)
-                    .unwrap()
-            };
-
-            let mut wtxn = env.write_txn().unwrap();
-            let db = env.create_database(&mut wtxn, None).unwrap();
-            wtxn.commit().unwrap();
+    if workers == 0 {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::InvalidValue,
+                "at least one worker is required (--threads, --readers or --writers)",
+            )
+            .exit();
+    }
 
-            GenericDatabase::Heed { db, env }
-        }
-        Backend::Fjall => {
-            use fjall::{
-                compaction::{Levelled, SizeTiered, Strategy},
-                BlockCache, PartitionCreateOptions,
-            };
+    // NOTE: Crash-recovery runs are allowed, because they verify acknowledged writes
+    if args.workload != Workload::TaskC
+        && args.fsync
+        && !args.crash_recovery
+        && (args.backend == Backend::Sled/*|| args.backend == Backend::Bloodstone*/)
+    {
+        panic!("Sled doesn't fsync... (use --crash-recovery to verify)");
+    }
 
-            let compaction_strategy: Arc<dyn Strategy + Send + Sync> = match args.lsm_compaction {
-                rust_storage_bench::LsmCompaction::Leveled => Arc::new(Levelled::default()),
-                rust_storage_bench::LsmCompaction::Tiered => Arc::new(SizeTiered::default()),
-            };
+    let backend = match args.backend {
+        Backend::Fjall => format!("{} {}", args.backend, args.lsm_compaction),
+        _ => args.backend.to_string(),
+    };
 
-            let config = fjall::Config::new(&data_dir)
-                .fsync_ms(if args.fsync { None } else { Some(1_000) })
-                .block_cache(BlockCache::with_capacity_bytes(args.cache_size.into()).into());
+    if args.crash_recovery && !args.crash_child {
+        recovery::run(&args, &backend);
+        return;
+    }
 
-            let create_opts =
-                PartitionCreateOptions::default().block_size(args.lsm_block_size.into());
+    if args.open_bench && args.open_child {
+        open_bench::run_child(&args);
+        return;
+    }
 
-            let keyspace = config.open().unwrap();
-            let db = keyspace.open_partition("data", create_opts).unwrap();
-            db.set_compaction_strategy(compaction_strategy);
+    if args.open_bench {
+        open_bench::run(&args, &backend);
+        return;
+    }
 
-            GenericDatabase::Fjall { keyspace, db }
-        }
-        Backend::Sled => GenericDatabase::Sled(
-            sled::Config::new()
-                .path(&data_dir)
-                .flush_every_ms(if args.fsync { None } else { Some(1_000) })
-                .cache_capacity(args.cache_size as u64)
-                .open()
-                .unwrap(),
-        ),
-        // Backend::Bloodstone => GenericDatabase::Bloodstone(
-        //     bloodstone::Config::new()
-        //         .cache_capacity_bytes(args.cache_size as usize)
-        //         .path(&data_dir)
-        //         .open()
-        //         .unwrap(),
-        // ),
-        Backend::JammDb => {
-            create_dir_all(&data_dir).unwrap();
-
-            let db = jammdb::DB::open(data_dir.join("data.db")).unwrap();
-            let tx = db.tx(true).unwrap();
-            let _ = tx.create_bucket("data").unwrap();
-            tx.commit().unwrap();
-
-            GenericDatabase::Jamm(db)
-        }
+    let data_dir = db::data_dir(&args);
 
-        Backend::Persy => {
-            use persy::{Config, Persy, PersyId, ValueMode};
+    if data_dir.exists() {
+        remove_dir_all(&data_dir).unwrap();
+    }
 
-            create_dir_all(&data_dir).unwrap();
+    let db = db::open(&args, &data_dir);
 
-            Persy::create(data_dir.join("data.persy")).unwrap();
+    let mut plan = Plan::new(&args);
 
-            let mut cfg = Config::default();
-            cfg.change_cache_size(args.cache_size.into());
-            let db = Persy::open(data_dir.join("data.persy"), cfg).unwrap();
+    if args.crash_child && args.fsync {
+        plan = plan.with_ack_log(AckLog::create(recovery::ack_log_path(&data_dir)));
+    }
 
-            let mut tx = db.begin().unwrap();
-            tx.create_segment("data").unwrap();
-            tx.create_index::<String, PersyId>("primary", ValueMode::Replace)
-                .unwrap();
-            let prepared = tx.prepare().unwrap();
-            prepared.commit().unwrap();
+    let db = DatabaseWrapper::new(db, plan.worker_count(), &args);
 
-            GenericDatabase::Persy(db)
-        }
-        Backend::Redb => {
-            create_dir_all(&data_dir).unwrap();
-
-            GenericDatabase::Redb(Arc::new(
-                redb::Builder::new()
-                    .set_cache_size(args.cache_size as usize)
-                    .create(data_dir.join("my_db.redb"))
-                    .unwrap(),
-            ))
-        }
-        Backend::Nebari => {
-            use nebari::{
-                tree::{Root, Unversioned},
-                Config,
-            };
+    let output = Output::create(&args.out);
+    let rate = TargetRate::new(&args);
+    let stalls = Arc::new(Mutex::new(StallTracker::new(&args)));
 
-            create_dir_all(&data_dir).unwrap();
+    memory_limit::start(db.clone(), &args, backend.clone(), output.clone());
 
-            let roots = Config::default_for(data_dir.join("db.nebari"))
-                .open()
-                .unwrap();
-            let tree = roots.tree(Unversioned::tree("data")).unwrap();
+    {
+        let stalls = stalls.clone();
+        let backend = backend.clone();
+        let output = output.clone();
 
-            GenericDatabase::Nebari { roots, tree }
-        }
-    };
+        shutdown::on_exit(move || {
+            let summary = stalls
+                .lock()
+                .expect("lock is poisoned")
+                .summary(unix_timestamp().as_micros() as u64, backend);
 
-    let db = DatabaseWrapper {
-        inner: db,
-        write_ops: Default::default(),
-        read_ops: Default::default(),
-        delete_ops: Default::default(),
-        scan_ops: Default::default(),
-        read_latency: Default::default(),
-        write_latency: Default::default(),
-    };
+            output.write(&Record::Stalls(Box::new(summary)));
+        });
+    }
 
     {
-        let db = db.clone();
-        let args = args.clone();
-
-        std::thread::spawn(move || {
-            use std::sync::atomic::Ordering::Relaxed;
+        let data_dir = data_dir.clone();
+        let backend = backend.clone();
+        let output = output.clone();
 
-            let backend = match args.backend {
-                Backend::Fjall => format!("{} {}", args.backend, args.lsm_compaction),
-                _ => args.backend.to_string(),
+        shutdown::on_exit(move || {
+            let Ok(usage) = DiskUsage::measure(&data_dir) else {
+                return;
             };
 
-            let mut sys = sysinfo::System::new_all();
-            sys.refresh_all();
-
-            let pid = std::process::id();
-            let pid = Pid::from(pid as usize);
-
-            let mut file_writer = std::fs::File::create(&args.out).unwrap();
-
-            {
-                let json = serde_json::json!({
-                    "time_micro": unix_timestamp().as_micros(),
-                    "type": "system",
-                    "os": sysinfo::System::long_os_version(),
-                    "kernel": sysinfo::System::kernel_version(),
-                    "cpu": sys.global_cpu_info().brand(),
-                    "mem": sys.total_memory(),
-                });
-
-                writeln!(
-                    &mut file_writer,
-                    "{}",
-                    serde_json::to_string(&json).unwrap()
-                )
-                .unwrap();
-            }
-
-            {
-                let json = serde_json::json!({
-                    "time_micro": unix_timestamp().as_micros(),
-                    "type": "setup",
-                    "backend": backend.to_string(),
-                    "workload": args.workload,
-                    "threads": args.threads,
-                    "items": args.items,
-                    "value_size": args.value_size,
-                    "cache_size_in_bytes": args.cache_size
-                });
-
-                writeln!(
-                    &mut file_writer,
-                    "{}",
-                    serde_json::to_string(&json).unwrap()
-                )
-                .unwrap();
-            }
-
-            let mut prev_write_ops = 0;
-            let mut prev_read_ops = 0;
-
-            loop {
-                if let Ok(du_bytes) = fs_extra::dir::get_size(&data_dir) {
-                    sys.refresh_all();
-
-                    let cpu = sys.global_cpu_info().cpu_usage();
-
-                    let proc = sys.processes();
-                    let child = proc.get(&pid).unwrap();
-
-                    let mem = child.memory() as f32;
-                    let disk = child.disk_usage();
-
-                    let write_ops = db.write_ops.load(Relaxed);
-                    let read_ops = db.read_ops.load(Relaxed);
-
-                    let dataset_size_bytes =
-                        write_ops as f64 * (args.key_size as f64 + args.value_size as f64);
-
-                    let space_amp = du_bytes as f64 / dataset_size_bytes;
-
-                    let write_amp = disk.total_written_bytes as f64 / dataset_size_bytes;
-
-                    let accumulated_write_latency = db
-                        .write_latency
-                        .fetch_min(0, std::sync::atomic::Ordering::Release);
-
-                    let accumulated_read_latency = db
-                        .read_latency
-                        .fetch_min(0, std::sync::atomic::Ordering::Release);
-
-                    let write_ops_since = write_ops - prev_write_ops;
-                    let read_ops_since = read_ops - prev_read_ops;
-
-                    let avg_write_latency = accumulated_write_latency / write_ops_since.max(1);
-                    let avg_read_latency = accumulated_read_latency / read_ops_since.max(1);
-
-                    let json = serde_json::json!({
-                        "backend": backend,
-                        "type": "metrics",
-                        "time_micro": unix_timestamp().as_micros(),
-                        "write_ops": write_ops,
-                        "read_ops": read_ops,
-                        "delete_ops": db.delete_ops,
-                        "scan_ops": db.scan_ops,
-                        "cpu": cpu,
-                        "mem_bytes": mem,
-                        "mem_mib": mem / 1024.0 / 1024.0,
-                        "disk_bytes_w": disk.total_written_bytes,
-                        "disk_bytes_r": disk.total_read_bytes,
-                        "disk_mib_w": (disk.total_written_bytes as f32) / 1024.0 / 1024.0,
-                        "disk_mib_r": (disk.total_read_bytes as f32) / 1024.0 / 1024.0,
-                        "du_bytes": du_bytes,
-                        "du_mib": (du_bytes as f32) / 1024.0 / 1024.0,
-                        "space_amp": space_amp,
-                        "write_amp": write_amp,
-                        "dataset_size": dataset_size_bytes,
-                        "avg_write_latency": avg_write_latency,
-                        "avg_read_latency": avg_read_latency,
-                    });
-
-                    prev_write_ops = write_ops;
-                    prev_read_ops = read_ops;
-
-                    writeln!(
-                        &mut file_writer,
-                        "{}",
-                        serde_json::to_string(&json).unwrap()
-                    )
-                    .unwrap();
-                }
-
-                // As minutes increase, decrease granularity
-                // to keep log files low(ish)
-                let sec = args.minutes as f32 / 2.0;
-                let duration = Duration::from_secs_f32(sec);
-                std::thread::sleep(duration);
-            }
+            output.write(&Record::DiskUsage(Box::new(DiskUsageRecord {
+                schema_version: SCHEMA_VERSION,
+                time_micro: unix_timestamp().as_micros() as u64,
+                backend,
+                total: usage.total(),
+                categories: usage.by_category(),
+                files: usage.files,
+            })));
         });
     }
 
-    match args.workload {
-        Workload::TaskA => {
-            let users = args.threads;
-
-            {
-                let mut rng = rand::thread_rng();
-
-                for idx in 0..users {
-                    let user_id = format!("user{idx:0>2}");
-
-                    for x in 0..args.items {
-                        let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                        for _ in 0..args.value_size {
-                            val.push(rng.gen::<u8>());
-                        }
-
-                        let key = format!("{user_id}:{x:0>10}");
-                        let key = key.as_bytes();
-
-                        db.insert(key, &val, false, args.clone());
-                    }
-                }
-            }
-
-            let threads = (0..users)
-                .map(|idx| {
-                    let args = args.clone();
-                    let db = db.clone();
-                    let user_id = format!("user{idx:0>2}");
-
-                    std::thread::spawn(move || {
-                        let mut rng = rand::thread_rng();
-
-                        let zipf = ZipfDistribution::new((args.items - 1) as usize, 0.99).unwrap();
-
-                        loop {
-                            let x = zipf.sample(&mut rng);
-                            let key = format!("{user_id}:{x:0>10}");
-                            let key = key.as_bytes();
-
-                            let choice: f32 = rng.gen_range(0.0..1.0);
-
-                            if choice > 0.5 {
-                                let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                                for _ in 0..args.value_size {
-                                    val.push(rng.gen::<u8>());
-                                }
-
-                                db.insert(key, &val, args.fsync, args.clone());
-                            } else {
-                                db.get(key).unwrap();
-                            }
-                        }
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            start_killer(args.minutes.into());
-
-            for t in threads {
-                t.join().unwrap();
-            }
-        }
-
-        Workload::TaskB => {
-            let users = args.threads;
-
-            {
-                let mut rng = rand::thread_rng();
-
-                for idx in 0..users {
-                    let user_id = format!("user{idx:0>2}");
-
-                    for x in 0..args.items {
-                        let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                        for _ in 0..args.value_size {
-                            val.push(rng.gen::<u8>());
-                        }
-
-                        let key = format!("{user_id}:{x:0>10}");
-                        let key = key.as_bytes();
-
-                        db.insert(key, &val, false, args.clone());
-                    }
-                }
-            }
-
-            let threads = (0..users)
-                .map(|idx| {
-                    let args = args.clone();
-                    let db = db.clone();
-                    let user_id = format!("user{idx:0>2}");
-
-                    std::thread::spawn(move || {
-                        let mut rng = rand::thread_rng();
-
-                        let zipf = ZipfDistribution::new((args.items - 1) as usize, 0.99).unwrap();
-
-                        loop {
-                            let x = zipf.sample(&mut rng);
-                            let key = format!("{user_id}:{x:0>10}");
-                            let key = key.as_bytes();
+    #[cfg(feature = "heap-stats")]
+    if args.snapshot_heap {
+        let backend = backend.clone();
+        let output = output.clone();
+
+        shutdown::on_exit(move || {
+            use rust_storage_bench::record::HeapProfileRecord;
+
+            let heap = heap::stats();
+
+            output.write(&Record::HeapProfile(Box::new(HeapProfileRecord {
+                schema_version: SCHEMA_VERSION,
+                time_micro: unix_timestamp().as_micros() as u64,
+                backend,
+                live_bytes: heap.live_bytes,
+                peak_bytes: heap.peak_bytes,
+                allocations: heap.allocations,
+                size_classes: heap::profile(),
+            })));
+        });
+    }
 
-                            let choice: f32 = rng.gen_range(0.0..1.0);
+    #[cfg(all(feature = "sync-trace", target_os = "linux"))]
+    {
+        let db = db.clone();
+        let backend = backend.clone();
+        let output = output.clone();
+
+        shutdown::on_exit(move || {
+            use rust_storage_bench::record::SyncsRecord;
+
+            let syncs = sync_trace::snapshot();
+            let write_ops = db.stats.totals().write_ops;
+
+            output.write(&Record::Syncs(Box::new(SyncsRecord {
+                schema_version: SCHEMA_VERSION,
+                time_micro: unix_timestamp().as_micros() as u64,
+                backend,
+                write_ops,
+                syncs: syncs.total(),
+                syncs_per_write: (write_ops > 0).then(|| syncs.total() as f64 / write_ops as f64),
+                latency: syncs.by_kind(),
+            })));
+        });
+    }
 
-                            if choice > 0.95 {
-                                let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                                for _ in 0..args.value_size {
-                                    val.push(rng.gen::<u8>());
-                                }
+    {
+        let db = db.clone();
+        let backend = backend.clone();
+        let output = output.clone();
+
+        // NOTE: Also runs when the run is aborted by a failed operation
+        shutdown::on_exit(move || {
+            #[cfg(feature = "heed")]
+            write_heed_resizes(&db, &output, &backend);
+
+            let totals = db.stats.totals();
+
+            output.write(&Record::Errors(Box::new(ErrorsRecord {
+                schema_version: SCHEMA_VERSION,
+                time_micro: unix_timestamp().as_micros() as u64,
+                backend,
+                write_errors: totals.write_errors.to_map(),
+                read_errors: totals.read_errors.to_map(),
+                retries: totals.retries,
+                aborted_by: db::fatal_error().map(str::to_owned),
+            })));
+        });
+    }
 
-                                db.insert(key, &val, args.fsync, args.clone());
-                            } else {
-                                db.get(key).unwrap();
+    {
+        let db = db.clone();
+        let args = args.clone();
+        let backend = backend.clone();
+        let output = output.clone();
+        let roles = plan.roles().collect::<Vec<_>>();
+        let stalls = stalls.clone();
+
+        std::thread::Builder::new()
+            .name(format!("{THREAD_PREFIX}metrics"))
+            .spawn(move || {
+                let sample_interval = sample_interval(&args);
+
+                let mut sys = sysinfo::System::new();
+                sys.refresh_cpu();
+
+                let pid = std::process::id();
+                let pid = Pid::from(pid as usize);
+
+                write_setup(&output, &args, &backend, &data_dir);
+
+                let mut prev = Counters::default();
+                let mut prev_workers = db.stats.workers().collect::<Vec<_>>();
+                let mut prev_time = Instant::now();
+                let mut prev_scheduling = db.stats.scheduling_histogram();
+                let mut prev_io = procfs::IoCounters::read();
+                let mut prev_stat = procfs::ProcStat::read();
+                let mut threads = ThreadTracker::new();
+
+                #[cfg(feature = "heap-stats")]
+                let mut prev_heap = heap::stats();
+
+                #[cfg(all(feature = "sync-trace", target_os = "linux"))]
+                let mut prev_syncs = sync_trace::snapshot();
+
+                loop {
+                    if let Ok(usage) = DiskUsage::measure(&data_dir) {
+                        let size = usage.total();
+                        let du_bytes = size.allocated_bytes;
+
+                        // NOTE: Only refresh what is needed, refreshing all processes is expensive
+                        sys.refresh_cpu();
+
+                        let cpu = sys.global_cpu_info().cpu_usage();
+
+                        let io = procfs::IoCounters::read();
+                        let stat = procfs::ProcStat::read();
+                        let memory = procfs::MemoryStat::read();
+
+                        // Fall back to sysinfo where /proc is not available
+                        let fallback = if io.is_none() || stat.is_none() {
+                            sys.refresh_process(pid);
+                            sys.process(pid).map(|x| (x.memory(), x.disk_usage()))
+                        } else {
+                            None
+                        };
+
+                        let mem = stat
+                            .map(|x| x.rss_bytes)
+                            .or(fallback.map(|(mem, _)| mem))
+                            .unwrap_or_default() as f32;
+
+                        let (disk_bytes_w, disk_bytes_r) = match (io, fallback) {
+                            (Some(io), _) => (io.physical_write_bytes(), io.read_bytes),
+                            (None, Some((_, disk))) => {
+                                (disk.total_written_bytes, disk.total_read_bytes)
                             }
+                            (None, None) => (0, 0),
+                        };
+
+                        let totals = db.stats.totals();
+                        let workers = db.stats.workers().collect::<Vec<_>>();
+
+                        let elapsed = prev_time.elapsed().as_secs_f64();
+                        prev_time = Instant::now();
+
+                        let process_cpu = stat
+                            .zip(prev_stat)
+                            .map(|(now, prev)| now.cpu_percent(&prev, elapsed));
+
+                        let process_cpu_user = stat.zip(prev_stat).map(|(now, prev)| {
+                            procfs::cpu_percent(now.utime.saturating_sub(prev.utime), elapsed)
+                        });
+
+                        let process_cpu_system = stat.zip(prev_stat).map(|(now, prev)| {
+                            procfs::cpu_percent(now.stime.saturating_sub(prev.stime), elapsed)
+                        });
+
+                        let io_delta = io.zip(prev_io).map(|(now, prev)| now.delta(&prev));
+
+                        let write_ops = totals.write_ops;
+                        let read_ops = totals.read_ops;
+
+                        let dataset_size_bytes =
+                            write_ops as f64 * (args.key_size as f64 + args.value_size as f64);
+
+                        // NOTE: Amplifications are undefined until something was written
+                        let has_data = dataset_size_bytes > 0.0;
+
+                        let space_amp = has_data.then(|| du_bytes as f64 / dataset_size_bytes);
+
+                        let write_amp = has_data.then(|| disk_bytes_w as f64 / dataset_size_bytes);
+                        let logical_write_amp = io
+                            .filter(|_| has_data)
+                            .map(|x| x.wchar as f64 / dataset_size_bytes);
+
+                        let accumulated_write_latency = totals.write_latency - prev.write_latency;
+                        let accumulated_read_latency = totals.read_latency - prev.read_latency;
+
+                        let write_ops_since = write_ops - prev.write_ops;
+                        let read_ops_since = read_ops - prev.read_ops;
+
+                        let avg_write_latency = accumulated_write_latency / write_ops_since.max(1);
+                        let avg_read_latency = accumulated_read_latency / read_ops_since.max(1);
+
+                        let per_thread = workers
+                            .iter()
+                            .zip(&prev_workers)
+                            .zip(&roles)
+                            .enumerate()
+                            .map(|(idx, ((now, prev), role))| WorkerMetrics {
+                                thread: idx,
+                                role: *role,
+                                write_ops: now.write_ops,
+                                read_ops: now.read_ops,
+                                ops_per_sec: (now.ops() - prev.ops()) as f64 / elapsed,
+                            })
+                            .collect::<Vec<_>>();
+
+                        let mut record = MetricsRecord {
+                            schema_version: SCHEMA_VERSION,
+                            time_micro: unix_timestamp().as_micros() as u64,
+                            backend: backend.clone(),
+                            write_ops,
+                            read_ops,
+                            delete_ops: totals.delete_ops,
+                            scan_ops: totals.scan_ops,
+                            ops_per_sec: (totals.ops() - prev.ops()) as f64 / elapsed,
+                            write_ops_per_sec: write_ops_since as f64 / elapsed,
+                            read_ops_per_sec: read_ops_since as f64 / elapsed,
+                            delete_ops_per_sec: (totals.delete_ops - prev.delete_ops) as f64
+                                / elapsed,
+                            scan_ops_per_sec: (totals.scan_ops - prev.scan_ops) as f64 / elapsed,
+                            avg_write_latency,
+                            avg_read_latency,
+                            write_errors: totals.write_errors.total(),
+                            read_errors: totals.read_errors.total(),
+                            errors: ErrorMetrics {
+                                write: totals.write_errors.to_map(),
+                                read: totals.read_errors.to_map(),
+                            },
+                            retries: totals.retries,
+                            per_thread,
+                            cpu,
+                            process_cpu,
+                            process_cpu_user,
+                            process_cpu_system,
+                            process_cpu_user_secs: stat.map(|x| procfs::ticks_to_secs(x.utime)),
+                            process_cpu_system_secs: stat.map(|x| procfs::ticks_to_secs(x.stime)),
+                            threads: threads.tick(elapsed),
+                            mem_bytes: mem,
+                            mem_mib: mem / 1024.0 / 1024.0,
+                            memory,
+                            minor_faults: stat.map(|x| x.minor_faults),
+                            major_faults: stat.map(|x| x.major_faults),
+                            vsize_bytes: stat.map(|x| x.vsize_bytes),
+                            os_threads: stat.map(|x| x.threads),
+                            disk_bytes_w,
+                            disk_bytes_r,
+                            disk_mib_w: (disk_bytes_w as f32) / 1024.0 / 1024.0,
+                            disk_mib_r: (disk_bytes_r as f32) / 1024.0 / 1024.0,
+                            io,
+                            io_delta,
+                            du_bytes,
+                            du_mib: (du_bytes as f32) / 1024.0 / 1024.0,
+                            apparent_bytes: size.apparent_bytes,
+                            disk_usage: Some(usage.by_category()),
+                            space_amp,
+                            write_amp,
+                            logical_write_amp,
+                            dataset_size: dataset_size_bytes,
+                            stall: Some(stalls.lock().expect("lock is poisoned").tick(
+                                unix_timestamp().as_micros() as u64,
+                                elapsed,
+                                write_ops,
+                                db.compaction_state(),
+                            )),
+                            syncs: None,
+                            syncs_per_write: None,
+                            heap: None,
+                            backend_stats: None,
+                            avg_scheduling_delay: None,
+                            p99_scheduling_delay: None,
+                        };
+
+                        #[cfg(all(feature = "sync-trace", target_os = "linux"))]
+                        {
+                            let syncs = sync_trace::snapshot();
+                            let delta = syncs.delta(&prev_syncs);
+
+                            record.syncs = Some(delta.by_kind());
+                            record.syncs_per_write = (write_ops_since > 0)
+                                .then(|| delta.total() as f64 / write_ops_since as f64);
+
+                            prev_syncs = syncs;
                         }
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            start_killer(args.minutes.into());
-
-            for t in threads {
-                t.join().unwrap();
-            }
-        }
-
-        Workload::TaskC => {
-            let mut rng = rand::thread_rng();
-
-            for x in 0..args.items {
-                let key = (x as u64).to_be_bytes();
-
-                let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                for _ in 0..args.value_size {
-                    val.push(rng.gen::<u8>());
-                }
-
-                db.insert(&key, &val, false, args.clone());
-            }
-
-            start_killer(args.minutes.into());
-
-            let zipf = ZipfDistribution::new((args.items - 1) as usize, 0.99).unwrap();
-
-            loop {
-                let x = zipf.sample(&mut rng);
-                let key = (x as u64).to_be_bytes();
-
-                db.get(&key).unwrap();
-            }
-        }
 
-        Workload::TaskD => {
-            let users = args.threads;
-
-            {
-                let mut rng = rand::thread_rng();
-
-                for idx in 0..users {
-                    let user_id = format!("user{idx:0>2}");
-
-                    for x in 0..args.items {
-                        let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                        for _ in 0..args.value_size {
-                            val.push(rng.gen::<u8>());
+                        #[cfg(feature = "heap-stats")]
+                        if args.snapshot_heap {
+                            let heap = heap::stats();
+                            record.heap = Some(heap.to_metrics(&prev_heap, elapsed));
+                            prev_heap = heap;
                         }
 
-                        let key = format!("{user_id}:{x:0>10}");
-                        let key = key.as_bytes();
-
-                        db.insert(key, &val, false, args.clone());
-                    }
-                }
-            }
-
-            let threads = (0..users)
-                .map(|idx| {
-                    let args = args.clone();
-                    let db = db.clone();
-                    let user_id = format!("user{idx:0>2}");
-
-                    std::thread::spawn(move || {
-                        let mut rng = rand::thread_rng();
-                        let mut records = args.items;
-
-                        loop {
-                            let choice: f32 = rng.gen_range(0.0..1.0);
-
-                            if choice > 0.95 {
-                                let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                                for _ in 0..args.value_size {
-                                    val.push(rng.gen::<u8>());
-                                }
-
-                                let key = format!("{user_id}:{records:0>10}");
-                                let key = key.as_bytes();
-
-                                db.insert(key, &val, args.fsync, args.clone());
-                                records += 1;
-                            } else {
-                                let key = format!("{user_id}:{:0>10}", records - 1);
-                                let key = key.as_bytes();
-
-                                db.get(key).unwrap();
-                            }
+                        if args.backend_stats {
+                            record.backend_stats = db.backend_stats();
                         }
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            start_killer(args.minutes.into());
-
-            for t in threads {
-                t.join().unwrap();
-            }
-        }
 
-        Workload::TaskE => {
-            let users = args.threads;
+                        if args.driver != Driver::Threads {
+                            let scheduling = db.stats.scheduling_histogram();
+                            let delta = scheduling.delta(&prev_scheduling);
 
-            {
-                let mut rng = rand::thread_rng();
+                            let ops_since = totals.ops() - prev.ops();
+                            let delay_since = totals.scheduling_delay - prev.scheduling_delay;
 
-                for idx in 0..users {
-                    let user_id = format!("user{idx:0>2}");
+                            record.avg_scheduling_delay = Some(delay_since / ops_since.max(1));
+                            record.p99_scheduling_delay = Some(delta.quantile(0.99));
 
-                    for x in 0..args.items {
-                        let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                        for _ in 0..args.value_size {
-                            val.push(rng.gen::<u8>());
+                            prev_scheduling = scheduling;
                         }
 
-                        let key = format!("{user_id}:{x:0>10}");
-                        let key = key.as_bytes();
+                        prev = totals;
+                        prev_workers = workers;
+                        prev_io = io;
+                        prev_stat = stat;
 
-                        db.insert(key, &val, false, args.clone());
+                        output.write(&Record::Metrics(Box::new(record)));
                     }
-                }
-            }
-
-            let threads = (0..users)
-                .map(|idx| {
-                    let args = args.clone();
-                    let db = db.clone();
-                    let user_id = format!("user{idx:0>2}");
-
-                    std::thread::spawn(move || {
-                        let mut rng = rand::thread_rng();
-                        let mut records = args.items;
-
-                        loop {
-                            let choice: f32 = rng.gen_range(0.0..1.0);
-
-                            if choice < 0.95 {
-                                let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                                for _ in 0..args.value_size {
-                                    val.push(rng.gen::<u8>());
-                                }
 
-                                let key = format!("{user_id}:{records:0>10}");
-                                let key = key.as_bytes();
+                    #[cfg(feature = "heed")]
+                    write_heed_resizes(&db, &output, &backend);
 
-                                db.insert(key, &val, args.fsync, args.clone());
-                                records += 1;
-                            } else {
-                                let key = format!("{user_id}:{:0>10}", records - 1);
-                                let key = key.as_bytes();
-
-                                db.get(key).unwrap();
-                            }
-                        }
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            start_killer(args.minutes.into());
-
-            for t in threads {
-                t.join().unwrap();
-            }
-        }
-
-        Workload::TaskF => {
-            let users = args.threads;
-
-            {
-                let mut rng = rand::thread_rng();
-
-                for idx in 0..users {
-                    let user_id = format!("user{idx:0>2}");
-
-                    for x in 0..args.items {
-                        let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                        for _ in 0..args.value_size {
-                            val.push(rng.gen::<u8>());
-                        }
-
-                        let key = format!("{user_id:0>2}:{x:0>10}");
-                        let key = key.as_bytes();
-
-                        db.insert(key, &val, false, args.clone());
-                    }
+                    std::thread::sleep(sample_interval);
                 }
-            }
-
-            let threads = (0..users)
-                .map(|idx| {
-                    let args = args.clone();
-                    let db = db.clone();
-                    let user_id = format!("user{idx:0>2}");
-
-                    std::thread::spawn(move || {
-                        let mut rng = rand::thread_rng();
-                        let mut records = args.items;
-
-                        loop {
-                            let choice: f32 = rng.gen_range(0.0..1.0);
-
-                            if choice > 0.95 {
-                                let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                                for _ in 0..args.value_size {
-                                    val.push(rng.gen::<u8>());
-                                }
-
-                                let key = format!("{user_id}:{records:0>10}");
-                                let key = key.as_bytes();
-
-                                db.insert(key, &val, args.fsync, args.clone());
-                                records += 1;
-                            } else {
-                                let zipf =
-                                    ZipfDistribution::new((records - 1) as usize, 0.99).unwrap();
-                                let x = zipf.sample(&mut rng);
-
-                                let key = format!("{user_id}:{x:0>10}");
-                                let key = key.as_bytes();
-
-                                db.get(key).unwrap();
-                            }
-                        }
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            start_killer(args.minutes.into());
+            })
+            .expect("failed to spawn metrics thread");
+    }
 
-            for t in threads {
-                t.join().unwrap();
-            }
+    // Called once the data set is loaded
+    let start_run = || {
+        stalls
+            .lock()
+            .expect("lock is poisoned")
+            .start(db.stats.totals().write_ops, db.compaction_state());
+
+        if args.ramp {
+            ramp::start(
+                db.clone(),
+                args.clone(),
+                backend.clone(),
+                rate.clone(),
+                output.clone(),
+            );
         }
 
-        Workload::TaskG => {
-            let users = args.threads;
-
-            {
-                let mut rng = rand::thread_rng();
-
-                for idx in 0..users {
-                    let user_id = format!("user{idx:0>2}");
-
-                    for x in 0..args.items {
-                        let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                        for _ in 0..args.value_size {
-                            val.push(rng.gen::<u8>());
-                        }
-
-                        let key = format!("{user_id}:{x:0>10}");
-                        let key = key.as_bytes();
-
-                        db.insert(key, &val, false, args.clone());
-                    }
-                }
-            }
-
-            let threads = (0..users)
-                .map(|idx| {
-                    let args = args.clone();
-                    let db = db.clone();
-                    let user_id = format!("user{idx:0>2}");
-
-                    std::thread::spawn(move || {
-                        let mut rng = rand::thread_rng();
-                        let mut records = args.items;
-
-                        loop {
-                            let choice: f32 = rng.gen_range(0.0..1.0);
-
-                            if choice < 0.95 {
-                                let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
-                                for _ in 0..args.value_size {
-                                    val.push(rng.gen::<u8>());
-                                }
-
-                                let key = format!("{user_id}:{records:0>10}");
-                                let key = key.as_bytes();
+        start_killer(args.minutes.into());
+    };
 
-                                db.insert(key, &val, args.fsync, args.clone());
-                                records += 1;
-                            } else {
-                                let zipf =
-                                    ZipfDistribution::new((records - 1) as usize, 0.99).unwrap();
-                                let x = zipf.sample(&mut rng);
+    plan.load(&db, &args);
 
-                                let key = format!("{user_id}:{x:0>10}");
-                                let key = key.as_bytes();
+    let threads = plan.spawn(&db, &args, &rate);
 
-                                db.get(key).unwrap();
-                            }
-                        }
-                    })
-                })
-                .collect::<Vec<_>>();
+    start_run();
 
-            start_killer(args.minutes.into());
-
-            for t in threads {
-                t.join().unwrap();
-            }
-        }
+    for t in threads {
+        t.join().unwrap();
     }
 }
@@ -0,0 +1,256 @@
+use crate::{
+    db::DatabaseWrapper, output::Output, shutdown, threads::THREAD_PREFIX, unix_timestamp,
+};
+use rust_storage_bench::{
+    procfs,
+    record::{
+        MemoryLimitExceeded, MemoryLimitMode, MemoryLimitRecord, Record, RunStatus, SCHEMA_VERSION,
+    },
+    Args,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How often memory usage is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How the memory limit is enforced
+enum Mode {
+    /// The process runs in its own cgroup (v2), with `memory.high` set to the limit,
+    /// so the kernel reclaims and throttles instead of OOM-killing the process
+    Cgroup {
+        dir: PathBuf,
+        parent: PathBuf,
+
+        /// Whether the memory controller was enabled for the children of `parent` by us
+        enabled_controller: bool,
+    },
+
+    /// RSS of the process is polled
+    Rss,
+}
+
+impl Mode {
+    fn kind(&self) -> MemoryLimitMode {
+        match self {
+            Self::Cgroup { .. } => MemoryLimitMode::Cgroup,
+            Self::Rss => MemoryLimitMode::Rss,
+        }
+    }
+
+    /// Current memory usage, and whether the limit was exceeded
+    fn poll(&self, limit: u64) -> Option<(u64, bool)> {
+        match self {
+            Self::Cgroup { dir, .. } => {
+                let current = std::fs::read_to_string(dir.join("memory.current")).ok()?;
+                let current = current.trim().parse().ok()?;
+
+                // NOTE: `high` counts how often usage went over `memory.high`
+                let events = std::fs::read_to_string(dir.join("memory.events")).ok()?;
+                let high = events
+                    .lines()
+                    .find_map(|line| line.strip_prefix("high "))
+                    .and_then(|x| x.trim().parse::<u64>().ok())
+                    .unwrap_or_default();
+
+                Some((current, high > 0 || current > limit))
+            }
+            Self::Rss => {
+                let rss = procfs::MemoryStat::read()?.rss_bytes;
+                Some((rss, rss > limit))
+            }
+        }
+    }
+}
+
+/// Mount point of the cgroup v2 hierarchy, from `/proc/self/mountinfo`
+fn cgroup2_mount() -> Option<PathBuf> {
+    procfs::Mount::read_all()?
+        .into_iter()
+        .find(|mount| mount.fs_type == "cgroup2")
+        .map(|mount| mount.mount_point)
+}
+
+/// Moves the process into a new cgroup below its current one, with `memory.high` set to the limit
+///
+/// Because processes may only live in leaf cgroups once controllers are enabled for the children
+/// ("no internal processes" rule), the process is moved first, and the memory controller is
+/// enabled afterwards. Fails if cgroup v2 is not mounted, the memory controller is not delegated
+/// to the current cgroup, or other processes remain in it.
+fn enter_cgroup(limit: u64) -> std::io::Result<Mode> {
+    let not_found = |msg: &str| std::io::Error::new(std::io::ErrorKind::NotFound, msg);
+
+    let mount = cgroup2_mount().ok_or_else(|| not_found("cgroup v2 is not mounted"))?;
+
+    let own = std::fs::read_to_string("/proc/self/cgroup")?;
+    let own = own
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| not_found("process is not in a cgroup v2"))?;
+
+    let parent = mount.join(own.trim_start_matches('/'));
+
+    let has_memory = |file: &str| {
+        std::fs::read_to_string(parent.join(file))
+            .is_ok_and(|x| x.split_whitespace().any(|x| x == "memory"))
+    };
+
+    if !has_memory("cgroup.controllers") {
+        return Err(not_found(
+            "memory controller is not delegated to the current cgroup",
+        ));
+    }
+
+    let enabled_controller = !has_memory("cgroup.subtree_control");
+
+    let dir = parent.join(format!("storage-bench-{}", std::process::id()));
+
+    std::fs::create_dir(&dir)?;
+
+    let result = std::fs::write(dir.join("cgroup.procs"), std::process::id().to_string())
+        .and_then(|()| {
+            if enabled_controller {
+                std::fs::write(parent.join("cgroup.subtree_control"), "+memory")
+            } else {
+                Ok(())
+            }
+        })
+        .and_then(|()| std::fs::write(dir.join("memory.high"), limit.to_string()));
+
+    if let Err(e) = result {
+        leave_cgroup(&dir, &parent, enabled_controller);
+        return Err(e);
+    }
+
+    Ok(Mode::Cgroup {
+        dir,
+        parent,
+        enabled_controller,
+    })
+}
+
+/// Moves the process back and removes its cgroup (best effort)
+fn leave_cgroup(dir: &Path, parent: &Path, enabled_controller: bool) {
+    // NOTE: The process can only move back once no controllers are enabled for the children
+    if enabled_controller {
+        let _ = std::fs::write(parent.join("cgroup.subtree_control"), "-memory");
+    }
+
+    let _ = std::fs::write(parent.join("cgroup.procs"), std::process::id().to_string());
+
+    if let Err(e) = std::fs::remove_dir(dir) {
+        log::warn!("failed to remove cgroup {dir:?}: {e}");
+    }
+}
+
+struct State {
+    peak_bytes: u64,
+    exceeded: Option<MemoryLimitExceeded>,
+}
+
+/// Enforces `--memory-limit`
+///
+/// Once memory usage exceeds the limit, the run is ended and marked as failed,
+/// so the result is recorded instead of the process being silently killed by the OOM killer.
+pub fn start(db: DatabaseWrapper, args: &Args, backend: String, output: Output) {
+    let Some(limit) = args.memory_limit else {
+        return;
+    };
+
+    let mode = match enter_cgroup(limit) {
+        Ok(mode) => mode,
+        Err(e) => {
+            log::warn!("cannot use a cgroup v2 memory limit ({e}), monitoring RSS instead");
+            Mode::Rss
+        }
+    };
+
+    log::info!(
+        "enforcing memory limit of {limit} bytes using {:?}",
+        mode.kind()
+    );
+
+    let mode = Arc::new(mode);
+
+    let state = Arc::new(Mutex::new(State {
+        peak_bytes: 0,
+        exceeded: None,
+    }));
+
+    {
+        let mode = mode.clone();
+        let state = state.clone();
+
+        shutdown::on_exit(move || {
+            let state = state.lock().expect("lock is poisoned");
+
+            let status = if state.exceeded.is_some() {
+                RunStatus::Failed
+            } else {
+                RunStatus::Ok
+            };
+
+            output.write(&Record::MemoryLimit(Box::new(MemoryLimitRecord {
+                schema_version: SCHEMA_VERSION,
+                time_micro: unix_timestamp().as_micros() as u64,
+                backend,
+                limit_bytes: limit,
+                mode: mode.kind(),
+                status,
+                peak_bytes: state.peak_bytes,
+                exceeded: state.exceeded.clone(),
+            })));
+
+            if let Mode::Cgroup {
+                dir,
+                parent,
+                enabled_controller,
+            } = &*mode
+            {
+                leave_cgroup(dir, parent, *enabled_controller);
+            }
+        });
+    }
+
+    std::thread::Builder::new()
+        .name(format!("{THREAD_PREFIX}memlimit"))
+        .spawn(move || {
+            let start = Instant::now();
+
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let Some((usage_bytes, exceeded)) = mode.poll(limit) else {
+                    log::warn!("failed to read memory usage, not enforcing memory limit");
+                    return;
+                };
+
+                let mut state = state.lock().expect("lock is poisoned");
+                state.peak_bytes = state.peak_bytes.max(usage_bytes);
+
+                if exceeded {
+                    let totals = db.stats.totals();
+
+                    log::error!(
+                        "memory limit of {limit} bytes exceeded ({usage_bytes} bytes) after {} ops",
+                        totals.ops()
+                    );
+
+                    state.exceeded = Some(MemoryLimitExceeded {
+                        time_micro: unix_timestamp().as_micros() as u64,
+                        elapsed_secs: start.elapsed().as_secs_f64(),
+                        usage_bytes,
+                        write_ops: totals.write_ops,
+                        read_ops: totals.read_ops,
+                    });
+
+                    drop(state);
+                    shutdown::exit(1);
+                }
+            }
+        })
+        .expect("failed to spawn memory limit thread");
+}
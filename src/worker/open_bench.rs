@@ -0,0 +1,228 @@
+use crate::{db, db::DatabaseWrapper, output::Output, unix_timestamp, write_setup};
+use rand::Rng;
+use rust_storage_bench::{
+    disk_usage::DiskUsage,
+    record::{OpenRecord, PageCache, Record, SCHEMA_VERSION},
+    Args,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::remove_dir_all,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::Instant,
+};
+use sysinfo::Pid;
+
+/// Marker file describing the data set in the data folder, so it can be reused
+fn marker_path(data_dir: &Path) -> PathBuf {
+    let mut path = data_dir.as_os_str().to_owned();
+    path.push(".dataset");
+    path.into()
+}
+
+/// Recursively lists all files in a folder
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .flat_map(|entry| {
+            let path = entry.path();
+
+            if path.is_dir() {
+                files(&path)
+            } else {
+                vec![path]
+            }
+        })
+        .collect()
+}
+
+/// Tries to evict the data set from the OS page cache, and returns the method that worked
+///
+/// Dropping all caches needs root; otherwise, every file of the data set is synced and
+/// the kernel is advised to evict its (now clean) pages.
+#[cfg(target_os = "linux")]
+fn drop_page_cache(data_dir: &Path) -> PageCache {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: sync has no preconditions
+    unsafe { libc::sync() };
+
+    if std::fs::write("/proc/sys/vm/drop_caches", "1").is_ok() {
+        return PageCache::DropCaches;
+    }
+
+    for path in files(data_dir) {
+        let Ok(file) = std::fs::File::open(&path) else {
+            continue;
+        };
+
+        // SAFETY: The file descriptor is valid while `file` is alive
+        let result =
+            unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+
+        if result != 0 {
+            log::warn!("posix_fadvise failed for {path:?}: {result}");
+        }
+    }
+
+    PageCache::Fadvise
+}
+
+#[cfg(not(target_os = "linux"))]
+fn drop_page_cache(_data_dir: &Path) -> PageCache {
+    log::warn!("Dropping the page cache is not supported on this platform");
+    PageCache::None
+}
+
+fn process_memory(sys: &mut sysinfo::System) -> u64 {
+    let pid = Pid::from(std::process::id() as usize);
+    sys.refresh_process(pid);
+    sys.process(pid).map(|p| p.memory()).unwrap_or_default()
+}
+
+/// Describes every setting that shapes the data set on disk
+fn dataset(args: &Args, backend: &str) -> String {
+    format!(
+        "backend={backend} compaction={} items={} key_size={} value_size={} lsm_block_size={} heed_map_size={} backend_opts={:?}",
+        args.lsm_compaction,
+        args.items,
+        args.key_size,
+        args.value_size,
+        args.lsm_block_size,
+        args.heed_map_size,
+        args.backend_opts,
+    )
+}
+
+/// Loads `--items` items into a fresh data folder, unless it already holds the same data set
+fn prepare(args: &Arc<Args>, backend: &str, data_dir: &Path) {
+    let marker = marker_path(data_dir);
+    let dataset = dataset(args, backend);
+
+    if data_dir.exists() && std::fs::read_to_string(&marker).is_ok_and(|x| x == dataset) {
+        log::info!("Reusing existing data set in {data_dir:?}");
+        return;
+    }
+
+    if data_dir.exists() {
+        remove_dir_all(data_dir).unwrap();
+    }
+
+    log::info!("Loading {} items into {data_dir:?}", args.items);
+
+    let db = DatabaseWrapper::new(db::open(args, data_dir), 0, args);
+    let mut rng = rand::thread_rng();
+
+    for x in 0..u64::from(args.items) {
+        let mut val: Vec<u8> = Vec::with_capacity(args.value_size as usize);
+        for _ in 0..args.value_size {
+            val.push(rng.gen::<u8>());
+        }
+
+        db.insert(&x.to_be_bytes(), &val, false)
+            .expect("failed to load the data set");
+    }
+
+    db.close();
+
+    std::fs::write(&marker, dataset).unwrap();
+}
+
+/// Measurements of a single open, reported by the child process on stdout
+#[derive(Serialize, Deserialize)]
+struct OpenSample {
+    open_micros: u64,
+    first_read_micros: u64,
+    mem_bytes: u64,
+}
+
+/// Opens the data set once and reads a random key (with `--open-child`)
+pub fn run_child(args: &Args) {
+    let data_dir = db::data_dir(args);
+    let key = rand::thread_rng()
+        .gen_range(0..u64::from(args.items))
+        .to_be_bytes();
+
+    let start = Instant::now();
+
+    let db = DatabaseWrapper::new(db::open(args, &data_dir), 0, args);
+    let open_time = start.elapsed();
+
+    let item = db.get(&key).expect("failed to read key");
+    assert!(item.is_some(), "item should exist");
+    let first_read_time = start.elapsed();
+
+    let sample = OpenSample {
+        open_micros: open_time.as_micros() as u64,
+        first_read_micros: first_read_time.as_micros() as u64,
+        mem_bytes: process_memory(&mut sysinfo::System::new()),
+    };
+
+    db.close();
+
+    println!(
+        "{}",
+        serde_json::to_string(&sample).expect("sample should serialize")
+    );
+}
+
+/// Runs the open-time benchmark
+///
+/// Repeatedly opens the data set, measuring the time to open, the time until
+/// the first read returns and the process memory after opening; every round
+/// is run once with a warm and once with a cold page cache.
+///
+/// Every open runs in a fresh child process (with `--open-child`),
+/// so memory is not carried over between rounds.
+pub fn run(args: &Arc<Args>, backend: &str) {
+    let data_dir = db::data_dir(args);
+    prepare(args, backend, &data_dir);
+
+    let output = Output::create(&args.out);
+    write_setup(&output, args, backend, &data_dir);
+
+    for round in 0..args.open_rounds {
+        for cold in [false, true] {
+            let cache = if cold {
+                drop_page_cache(&data_dir)
+            } else {
+                PageCache::Warm
+            };
+
+            let child = Command::new(std::env::current_exe().unwrap())
+                .args(std::env::args_os().skip(1))
+                .arg("--open-child")
+                .stderr(Stdio::inherit())
+                .output()
+                .unwrap();
+
+            assert!(child.status.success(), "child failed: {}", child.status);
+
+            let sample: OpenSample =
+                serde_json::from_slice(&child.stdout).expect("child should report a sample");
+
+            output.write(&Record::Open(Box::new(OpenRecord {
+                schema_version: SCHEMA_VERSION,
+                time_micro: unix_timestamp().as_micros() as u64,
+                backend: backend.to_owned(),
+                items: args.items,
+                value_size: args.value_size,
+                round,
+                cold,
+                cache,
+                open_micros: sample.open_micros,
+                first_read_micros: sample.first_read_micros,
+                mem_bytes: sample.mem_bytes,
+                du_bytes: DiskUsage::measure(&data_dir)
+                    .ok()
+                    .map(|x| x.total().allocated_bytes),
+            })));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use rust_storage_bench::record::Record;
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// JSONL result file, shared between the metrics thread and other reporters
+#[derive(Clone)]
+pub struct Output(Arc<Mutex<File>>);
+
+impl Output {
+    pub fn create<P: AsRef<Path>>(path: P) -> Self {
+        let file = File::create(path).unwrap();
+        Self(Arc::new(Mutex::new(file)))
+    }
+
+    /// Opens an existing result file, keeping its records
+    pub fn append<P: AsRef<Path>>(path: P) -> Self {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        Self(Arc::new(Mutex::new(file)))
+    }
+
+    /// Appends a single record as one line
+    pub fn write(&self, record: &Record) {
+        let json = serde_json::to_string(record).expect("record should serialize");
+
+        let mut file = self.0.lock().expect("lock is poisoned");
+        writeln!(file, "{json}").unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use rand::Rng;
+use rand_distr::{Distribution, Exp1};
+use rust_storage_bench::{Args, Arrival};
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering::Relaxed},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Target throughput (ops per second) shared by all workers
+///
+/// 0 means unlimited (closed loop). Can be changed while the benchmark is running,
+/// e.g. by the ramp controller.
+#[derive(Clone, Default)]
+pub struct TargetRate(Arc<AtomicU32>);
+
+impl TargetRate {
+    pub fn new(args: &Args) -> Self {
+        Self(Arc::new(AtomicU32::new(
+            args.ops_per_sec.unwrap_or_default(),
+        )))
+    }
+
+    pub fn get(&self) -> Option<u32> {
+        match self.0.load(Relaxed) {
+            0 => None,
+            ops_per_sec => Some(ops_per_sec),
+        }
+    }
+
+    pub fn set(&self, ops_per_sec: u32) {
+        self.0.store(ops_per_sec, Relaxed);
+    }
+}
+
+/// Paces the operations of a single worker thread
+///
+/// In closed-loop mode (no target rate), operations are issued back-to-back,
+/// and their latency is measured from the call into the storage engine.
+///
+/// In open-loop mode, operations are scheduled at a fixed rate (or Poisson arrivals),
+/// independent of how long previous operations took. The returned start time is
+/// the *intended* start time, so latencies include any time spent queueing behind
+/// a slow operation (avoiding coordinated omission).
+pub struct Pacer {
+    rate: TargetRate,
+    arrival: Arrival,
+
+    /// Number of workers sharing the target rate
+    workers: f64,
+
+    /// Intended start time of the next operation
+    next: Option<Instant>,
+}
+
+impl Pacer {
+    /// Creates a pacer for one of `workers` threads that share the target throughput
+    pub fn new(args: &Args, rate: TargetRate, workers: u32) -> Self {
+        Self {
+            rate,
+            arrival: args.arrival,
+            workers: f64::from(workers.max(1)),
+            next: None,
+        }
+    }
+
+    /// Waits until the next operation is due, and returns its intended start time
+    /// (`None` in closed-loop mode)
+    pub fn wait<R: Rng>(&mut self, rng: &mut R) -> Option<Instant> {
+        let intended = self.schedule(rng)?;
+
+        let now = Instant::now();
+        if intended > now {
+            std::thread::sleep(intended - now);
+        }
+
+        Some(intended)
+    }
+
+    /// Returns the intended start time of the next operation, without waiting for it
+    ///
+    /// Returns `None` in closed-loop mode, where the next operation is due immediately.
+    pub fn schedule<R: Rng>(&mut self, rng: &mut R) -> Option<Instant> {
+        let Some(ops_per_sec) = self.rate.get() else {
+            self.next = None;
+            return None;
+        };
+
+        let intended = *self.next.get_or_insert_with(Instant::now);
+
+        let interval = self.workers / f64::from(ops_per_sec);
+
+        let gap = match self.arrival {
+            Arrival::Constant => interval,
+            Arrival::Poisson => {
+                let x: f64 = Exp1.sample(rng);
+                interval * x
+            }
+        };
+
+        self.next = Some(intended + Duration::from_secs_f64(gap));
+
+        Some(intended)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn pacer(extra: &[&str]) -> Pacer {
+        let args = Args::parse_from(
+            [
+                "worker",
+                "--backend",
+                "sled",
+                "--workload",
+                "task-a",
+                "--items",
+                "1",
+                "--key-size",
+                "8",
+                "--value-size",
+                "8",
+            ]
+            .iter()
+            .chain(extra),
+        );
+
+        Pacer::new(&args, TargetRate::new(&args), 1)
+    }
+
+    #[test]
+    fn closed_loop_never_waits() {
+        let mut pacer = pacer(&[]);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..1_000 {
+            assert_eq!(None, pacer.schedule(&mut rng));
+        }
+
+        let start = Instant::now();
+        for _ in 0..1_000 {
+            assert_eq!(None, pacer.wait(&mut rng));
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn open_loop_schedules_at_target_rate() {
+        let mut pacer = pacer(&["--ops-per-sec", "1000"]);
+        let mut rng = rand::thread_rng();
+
+        let first = pacer.schedule(&mut rng).unwrap();
+        let second = pacer.schedule(&mut rng).unwrap();
+
+        assert_eq!(Duration::from_millis(1), second - first);
+    }
+}
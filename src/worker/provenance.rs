@@ -0,0 +1,45 @@
+use rust_storage_bench::{
+    procfs::Mount,
+    record::{BuildInfo, FilesystemInfo},
+};
+use std::path::Path;
+
+/// Returns `None` for values the build script could not determine
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+/// Build provenance, embedded by `build.rs`
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        git_commit: non_empty(env!("BENCH_GIT_COMMIT")),
+        git_dirty: env!("BENCH_GIT_DIRTY").parse().ok(),
+        rustc: non_empty(env!("BENCH_RUSTC_VERSION")),
+        profile: env!("BENCH_PROFILE").to_owned(),
+        target: env!("BENCH_TARGET").to_owned(),
+        features: env!("BENCH_FEATURES")
+            .split(',')
+            .filter(|x| !x.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        crates: env!("BENCH_CRATE_VERSIONS")
+            .split(';')
+            .filter_map(|x| x.split_once('='))
+            .map(|(name, version)| (name.to_owned(), version.to_owned()))
+            .collect(),
+    }
+}
+
+/// File system and mount options of the given path
+pub fn filesystem(path: &Path) -> Option<FilesystemInfo> {
+    let mount = Mount::of(path)?;
+
+    Some(FilesystemInfo {
+        mount_point: mount.mount_point.to_string_lossy().into_owned(),
+        fs_type: mount.fs_type,
+        source: mount.source,
+        mount_options: mount.options,
+        super_options: mount.super_options,
+    })
+}
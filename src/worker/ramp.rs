@@ -0,0 +1,135 @@
+use crate::{
+    db::DatabaseWrapper, histogram::Snapshot, output::Output, pacer::TargetRate, shutdown,
+    threads::THREAD_PREFIX, unix_timestamp,
+};
+use rust_storage_bench::{
+    record::{RampStepRecord, RampStop, RampSummaryRecord, Record, SCHEMA_VERSION},
+    Args,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct RampState {
+    steps: u32,
+
+    /// Highest target throughput that stayed within the latency SLO
+    max_sustainable: Option<u32>,
+
+    /// Whether the summary was already written
+    done: bool,
+}
+
+fn write_summary(
+    output: &Output,
+    args: &Args,
+    backend: &str,
+    state: &mut RampState,
+    reason: RampStop,
+) {
+    if state.done {
+        return;
+    }
+    state.done = true;
+
+    output.write(&Record::RampSummary(Box::new(RampSummaryRecord {
+        schema_version: SCHEMA_VERSION,
+        time_micro: unix_timestamp().as_micros() as u64,
+        backend: backend.to_owned(),
+        steps: state.steps,
+        max_sustainable_ops_per_sec: state.max_sustainable,
+        slo_p99_latency: args.slo_p99_us,
+        stopped_by: reason,
+    })));
+}
+
+fn latency_snapshot(db: &DatabaseWrapper) -> Snapshot {
+    let mut snapshot = db.stats.write_histogram();
+    snapshot.merge(&db.stats.read_histogram());
+    snapshot
+}
+
+/// Starts the ramp controller
+///
+/// Every step, the target throughput is increased by `--ramp-step-percent`,
+/// until the p99 latency of a step exceeds `--slo-p99-us`.
+/// The last target throughput within the SLO is reported as the maximum sustainable throughput.
+pub fn start(
+    db: DatabaseWrapper,
+    args: Arc<Args>,
+    backend: String,
+    rate: TargetRate,
+    output: Output,
+) {
+    let state = Arc::new(Mutex::new(RampState::default()));
+
+    {
+        let state = state.clone();
+        let output = output.clone();
+        let args = args.clone();
+        let backend = backend.clone();
+
+        shutdown::on_exit(move || {
+            let mut state = state.lock().expect("lock is poisoned");
+            write_summary(&output, &args, &backend, &mut state, RampStop::Timeout);
+        });
+    }
+
+    std::thread::Builder::new()
+        .name(format!("{THREAD_PREFIX}ramp"))
+        .spawn(move || {
+            let step_duration = Duration::from_secs(args.ramp_step_secs.into());
+
+            loop {
+                let target = rate.get().expect("ramp requires a target rate");
+
+                let before = latency_snapshot(&db);
+                let start = Instant::now();
+
+                std::thread::sleep(step_duration);
+
+                let latencies = latency_snapshot(&db).delta(&before);
+                let elapsed = start.elapsed().as_secs_f64();
+
+                let p99 = latencies.quantile(0.99);
+                let ok = p99 <= args.slo_p99_us;
+
+                let mut state = state.lock().expect("lock is poisoned");
+                state.steps += 1;
+
+                output.write(&Record::RampStep(Box::new(RampStepRecord {
+                    schema_version: SCHEMA_VERSION,
+                    time_micro: unix_timestamp().as_micros() as u64,
+                    backend: backend.clone(),
+                    step: state.steps,
+                    target_ops_per_sec: target,
+                    achieved_ops_per_sec: latencies.count() as f64 / elapsed,
+                    p50_latency: latencies.quantile(0.5),
+                    p99_latency: p99,
+                    max_latency: latencies.max(),
+                    slo_p99_latency: args.slo_p99_us,
+                    ok,
+                })));
+
+                if !ok {
+                    write_summary(&output, &args, &backend, &mut state, RampStop::Slo);
+                    drop(state);
+                    shutdown::exit(0);
+                }
+
+                state.max_sustainable = Some(target);
+
+                let next = u64::from(target) * (100 + u64::from(args.ramp_step_percent)) / 100;
+                let next = next.max(u64::from(target) + 1).min(u32::MAX.into()) as u32;
+
+                log::info!(
+                    "ramp: {target} ops/s within SLO (p99 = {p99}µs), next step: {next} ops/s"
+                );
+
+                rate.set(next);
+            }
+        })
+        .expect("failed to spawn ramp thread");
+}
@@ -0,0 +1,221 @@
+use crate::{db, db::DatabaseWrapper, output::Output, unix_timestamp};
+use rust_storage_bench::{
+    disk_usage::{Category, DiskUsage},
+    record::{Record, RecoveryRecord, SCHEMA_VERSION},
+    Args, Backend,
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Side file of the child process, listing every durable write that was acknowledged
+pub fn ack_log_path(data_dir: &Path) -> PathBuf {
+    let mut path = data_dir.as_os_str().to_owned();
+    path.push(".acks");
+    path.into()
+}
+
+/// Writes the sequence number into the first 8 bytes of a value
+///
+/// Values shorter than 8 bytes are left alone, so only their existence can be verified.
+pub fn stamp_sequence_number(value: &mut [u8], seq: u64) {
+    if let Some(prefix) = value.get_mut(..8) {
+        prefix.copy_from_slice(&seq.to_be_bytes());
+    }
+}
+
+fn read_sequence_number(value: &[u8]) -> Option<u64> {
+    let prefix = value.get(..8)?;
+    Some(u64::from_be_bytes(prefix.try_into().ok()?))
+}
+
+/// Log of acknowledged durable writes
+///
+/// Every write gets a sequence number (stored in its value) and every acknowledgement
+/// is a single unbuffered `write` of `<seq> <hex key>`, so it survives the process
+/// being killed (but not a power loss).
+pub struct AckLog {
+    file: Mutex<File>,
+    seq: AtomicU64,
+}
+
+impl AckLog {
+    pub fn create<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            file: Mutex::new(File::create(path).unwrap()),
+            seq: AtomicU64::new(1),
+        }
+    }
+
+    /// Returns the sequence number for the next write
+    pub fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Relaxed)
+    }
+
+    /// Records a write that was acknowledged as durable by the storage engine
+    pub fn ack(&self, seq: u64, key: &[u8]) {
+        let line = format!("{seq} {}\n", hex(key));
+
+        let mut file = self.file.lock().expect("lock is poisoned");
+        file.write_all(line.as_bytes()).unwrap();
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&s[idx..idx + 2], 16).unwrap())
+        .collect()
+}
+
+/// Reads the acknowledged writes as (sequence number, key) pairs
+fn read_acks(path: &Path) -> Vec<(u64, Vec<u8>)> {
+    let Ok(file) = File::open(path) else {
+        return vec![];
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| {
+            let (seq, key) = line.split_once(' ')?;
+
+            // NOTE: The last line may be torn if the process was killed mid-write
+            if key.is_empty() || key.len() % 2 != 0 {
+                return None;
+            }
+
+            Some((seq.parse().ok()?, unhex(key)))
+        })
+        .collect()
+}
+
+/// Size of the write-ahead log that needs to be replayed when reopening,
+/// or `None` for engines that do not keep a separate log
+fn wal_bytes(backend: Backend, usage: &DiskUsage) -> Option<u64> {
+    // NOTE: Journals are preallocated, so only count allocated blocks
+    let wal = || {
+        usage
+            .by_category()
+            .get(&Category::Wal)
+            .map_or(0, |size| size.allocated_bytes)
+    };
+
+    match backend {
+        Backend::Fjall => Some(wal()),
+
+        #[cfg(feature = "rocksdb")]
+        Backend::RocksDb => Some(wal()),
+
+        _ => None,
+    }
+}
+
+/// Runs a crash-recovery benchmark
+///
+/// The workload runs in a child process (with `--crash-child`), which is killed with SIGKILL
+/// after `--crash-after-secs`. Afterwards, the database is reopened, measuring recovery time,
+/// and every write the child acknowledged as durable is checked.
+pub fn run(args: &Args, backend: &str) {
+    let data_dir = db::data_dir(args);
+    let ack_path = ack_log_path(&data_dir);
+
+    if ack_path.exists() {
+        std::fs::remove_file(&ack_path).unwrap();
+    }
+
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args(std::env::args_os().skip(1))
+        .arg("--crash-child")
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_secs(args.crash_after_secs.into()));
+
+    if let Some(status) = child.try_wait().unwrap() {
+        panic!("Child exited before it could be killed: {status}");
+    }
+
+    log::info!("Killing child process {}", child.id());
+
+    // NOTE: Sends SIGKILL on Unix
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let usage = DiskUsage::measure(&data_dir).ok();
+    let wal_bytes = usage.as_ref().and_then(|x| wal_bytes(args.backend, x));
+    let du_bytes = usage.as_ref().map(|x| x.total().allocated_bytes);
+
+    let start = Instant::now();
+    let db = db::open(args, &data_dir);
+    let recovery_time = start.elapsed();
+
+    let db = DatabaseWrapper::new(db, 0, args);
+
+    let acks = read_acks(&ack_path);
+
+    // Latest acknowledged write of every key
+    let mut keys = BTreeMap::<&[u8], u64>::new();
+
+    for (seq, key) in &acks {
+        let entry = keys.entry(key).or_default();
+        *entry = (*entry).max(*seq);
+    }
+
+    let mut missing = vec![];
+    let mut stale = vec![];
+
+    // NOTE: If multiple threads update the same key (shared key space),
+    // the write with the highest sequence number is not necessarily the last one committed,
+    // so stale keys may be false positives in that case
+    for (key, seq) in keys.iter() {
+        match db.get(key).expect("failed to read key") {
+            None => missing.push(hex(key)),
+            Some(value) => {
+                if read_sequence_number(&value).is_some_and(|stored| stored < *seq) {
+                    stale.push(hex(key));
+                }
+            }
+        }
+    }
+
+    let lost = missing.len() + stale.len();
+
+    if lost > 0 {
+        log::error!(
+            "{lost} acknowledged synced writes were lost ({} missing, {} stale)",
+            missing.len(),
+            stale.len(),
+        );
+    }
+
+    Output::append(&args.out).write(&Record::Recovery(Box::new(RecoveryRecord {
+        schema_version: SCHEMA_VERSION,
+        time_micro: unix_timestamp().as_micros() as u64,
+        backend: backend.to_owned(),
+        fsync: args.fsync,
+        crash_after_secs: args.crash_after_secs,
+        recovery_micros: recovery_time.as_micros() as u64,
+        wal_bytes,
+        du_bytes,
+        acked_writes: acks.len(),
+        acked_keys: keys.len(),
+        lost_keys: lost,
+        missing_keys: missing.len(),
+        stale_keys: stale.len(),
+        lost_key_samples: missing.iter().chain(&stale).take(10).cloned().collect(),
+    })));
+}
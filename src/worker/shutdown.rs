@@ -0,0 +1,37 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering::SeqCst},
+    Mutex,
+};
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+
+static EXITING: AtomicBool = AtomicBool::new(false);
+
+/// Registers a function that runs once before the process exits
+///
+/// Used to write end-of-run summaries, because workers never return on their own.
+pub fn on_exit(f: impl FnOnce() + Send + 'static) {
+    HOOKS.lock().expect("lock is poisoned").push(Box::new(f));
+}
+
+/// Runs all exit hooks (in registration order) and exits the process
+///
+/// Only the first caller runs the hooks and exits; later callers (e.g. the memory limit watcher
+/// racing the metrics thread) block forever, so they cannot exit while summaries are still being written.
+pub fn exit(code: i32) -> ! {
+    if EXITING.swap(true, SeqCst) {
+        loop {
+            std::thread::park();
+        }
+    }
+
+    let hooks = std::mem::take(&mut *HOOKS.lock().expect("lock is poisoned"));
+
+    for hook in hooks {
+        hook();
+    }
+
+    std::process::exit(code);
+}
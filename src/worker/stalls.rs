@@ -0,0 +1,240 @@
+use crate::backend_stats::CompactionState;
+use rust_storage_bench::{
+    record::{StallPeriod, StallState, StallsRecord, SCHEMA_VERSION},
+    Args,
+};
+
+/// Detects write stalls from per-tick write throughput and engine-reported state
+///
+/// A tick is stalled if its write throughput drops below `--stall-threshold-percent`
+/// of the average throughput of all previous non-stalled ticks,
+/// or if the engine reports that it is stalling writes.
+pub struct StallTracker {
+    threshold: f64,
+
+    /// Ticks before the run has started (while loading) are ignored
+    started: bool,
+
+    /// The first tick after starting only partially covers the run, so it is skipped
+    partial_tick: bool,
+
+    /// Write operations at the previous tick (or at the start of the run)
+    prev_write_ops: u64,
+
+    /// Sum of write throughput of all non-stalled ticks
+    baseline_sum: f64,
+    baseline_ticks: u32,
+
+    current: Option<StallPeriod>,
+    periods: Vec<StallPeriod>,
+
+    prev_cumulative_stall_secs: Option<f64>,
+    engine_stall_secs: f64,
+
+    max_pending_compaction_bytes: Option<u64>,
+    max_l0_segments: Option<u64>,
+}
+
+impl StallTracker {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            threshold: f64::from(args.stall_threshold_percent) / 100.0,
+            started: false,
+            partial_tick: false,
+            prev_write_ops: 0,
+            baseline_sum: 0.0,
+            baseline_ticks: 0,
+            current: None,
+            periods: vec![],
+            prev_cumulative_stall_secs: None,
+            engine_stall_secs: 0.0,
+            max_pending_compaction_bytes: None,
+            max_l0_segments: None,
+        }
+    }
+
+    /// Starts tracking, once the data set is loaded
+    ///
+    /// Snapshots the write counter and the engine stall counter,
+    /// so writes and stalls of the load phase are not counted.
+    pub fn start(&mut self, write_ops: u64, state: Option<CompactionState>) {
+        self.started = true;
+        self.partial_tick = true;
+        self.prev_write_ops = write_ops;
+        self.prev_cumulative_stall_secs = state.and_then(|x| x.cumulative_stall_secs);
+    }
+
+    fn baseline(&self) -> Option<f64> {
+        (self.baseline_ticks > 0).then(|| self.baseline_sum / f64::from(self.baseline_ticks))
+    }
+
+    /// Records a tick and returns its stall state for the metrics record
+    pub fn tick(
+        &mut self,
+        now_micro: u64,
+        elapsed_secs: f64,
+        write_ops: u64,
+        state: Option<CompactionState>,
+    ) -> StallState {
+        let write_ops_per_sec = write_ops.saturating_sub(self.prev_write_ops) as f64 / elapsed_secs;
+        self.prev_write_ops = write_ops;
+
+        let active = self.started && !std::mem::take(&mut self.partial_tick);
+
+        let mut engine = false;
+
+        if let Some(state) = &state {
+            if self.started {
+                if let Some(secs) = state.cumulative_stall_secs {
+                    let prev = self.prev_cumulative_stall_secs.replace(secs);
+                    let delta = secs - prev.unwrap_or(secs);
+
+                    self.engine_stall_secs += delta;
+                    engine |= delta > 0.0;
+                }
+            }
+
+            engine |= state.write_stall;
+
+            self.max_pending_compaction_bytes = self
+                .max_pending_compaction_bytes
+                .max(state.pending_compaction_bytes);
+            self.max_l0_segments = self.max_l0_segments.max(state.l0_segments);
+        }
+
+        let baseline = self.baseline();
+
+        let throughput = active
+            && baseline.is_some_and(|baseline| write_ops_per_sec < baseline * self.threshold);
+
+        let engine = active && engine;
+        let stalled = throughput || engine;
+
+        if stalled {
+            let period = self.current.get_or_insert(StallPeriod {
+                start_micro: now_micro.saturating_sub((elapsed_secs * 1_000_000.0) as u64),
+                duration_secs: 0.0,
+                min_write_ops_per_sec: write_ops_per_sec,
+                throughput: false,
+                engine: false,
+            });
+
+            period.duration_secs += elapsed_secs;
+            period.min_write_ops_per_sec = period.min_write_ops_per_sec.min(write_ops_per_sec);
+            period.throughput |= throughput;
+            period.engine |= engine;
+        } else {
+            self.periods.extend(self.current.take());
+
+            if active {
+                self.baseline_sum += write_ops_per_sec;
+                self.baseline_ticks += 1;
+            }
+        }
+
+        StallState {
+            stalled,
+            throughput,
+            engine,
+            baseline_write_ops_per_sec: baseline,
+            pending_compaction_bytes: state.as_ref().and_then(|x| x.pending_compaction_bytes),
+            l0_segments: state.as_ref().and_then(|x| x.l0_segments),
+        }
+    }
+
+    /// Summary of all stall periods (including an ongoing one)
+    pub fn summary(&mut self, time_micro: u64, backend: String) -> StallsRecord {
+        self.periods.extend(self.current.take());
+
+        // NOTE: Summing an empty iterator of floats results in -0.0
+        let stalled_secs = self
+            .periods
+            .iter()
+            .fold(0.0, |acc, x| acc + x.duration_secs);
+        let longest_secs = self
+            .periods
+            .iter()
+            .map(|x| x.duration_secs)
+            .fold(0.0, f64::max);
+
+        StallsRecord {
+            schema_version: SCHEMA_VERSION,
+            time_micro,
+            backend,
+            threshold_percent: self.threshold * 100.0,
+            baseline_write_ops_per_sec: self.baseline(),
+            stall_count: self.periods.len(),
+            stalled_secs,
+            longest_stall_secs: longest_secs,
+            engine_stall_secs: self
+                .prev_cumulative_stall_secs
+                .map(|_| self.engine_stall_secs),
+            max_pending_compaction_bytes: self.max_pending_compaction_bytes,
+            max_l0_segments: self.max_l0_segments,
+            periods: self.periods.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn tracker() -> StallTracker {
+        let args = Args::parse_from([
+            "worker",
+            "--backend",
+            "sled",
+            "--workload",
+            "task-a",
+            "--items",
+            "1",
+            "--key-size",
+            "8",
+            "--value-size",
+            "8",
+        ]);
+
+        StallTracker::new(&args)
+    }
+
+    fn state(cumulative_stall_secs: f64) -> Option<CompactionState> {
+        Some(CompactionState {
+            write_stall: false,
+            cumulative_stall_secs: Some(cumulative_stall_secs),
+            pending_compaction_bytes: None,
+            l0_segments: None,
+        })
+    }
+
+    #[test]
+    fn ignore_load_phase() {
+        let mut tracker = tracker();
+        let mut write_ops = 0;
+        let mut now_micro = 0;
+
+        // Loading writes much faster than the run, and stalls in the engine
+        for tick in 1..=10 {
+            write_ops += 100_000;
+            now_micro += 1_000_000;
+            tracker.tick(now_micro, 1.0, write_ops, state(f64::from(tick)));
+        }
+
+        // The run starts in the middle of a tick
+        write_ops += 50_000;
+        tracker.start(write_ops, state(10.0));
+
+        for _ in 0..10 {
+            write_ops += 1_000;
+            now_micro += 1_000_000;
+            let stall = tracker.tick(now_micro, 1.0, write_ops, state(10.0));
+            assert!(!stall.stalled);
+        }
+
+        let summary = tracker.summary(now_micro, "sled".into());
+        assert_eq!(0, summary.stall_count);
+        assert_eq!(Some(0.0), summary.engine_stall_secs);
+        assert_eq!(Some(1_000.0), summary.baseline_write_ops_per_sec);
+    }
+}
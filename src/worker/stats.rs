@@ -0,0 +1,185 @@
+use crate::{
+    error::ErrorKind,
+    histogram::{Histogram, Snapshot},
+};
+use crossbeam_utils::CachePadded;
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+};
+
+const ERROR_KINDS: usize = ErrorKind::ALL.len();
+
+/// Failed operations, per error kind
+#[derive(Default)]
+pub struct ErrorCounters([AtomicU64; ERROR_KINDS]);
+
+impl ErrorCounters {
+    pub fn record(&self, kind: ErrorKind) {
+        self.0[kind.index()].fetch_add(1, Relaxed);
+    }
+}
+
+/// Point-in-time sum of error counters, per error kind
+#[derive(Clone, Copy, Default)]
+pub struct ErrorCounts([u64; ERROR_KINDS]);
+
+impl ErrorCounts {
+    fn add(&mut self, counters: &ErrorCounters) {
+        for (count, counter) in self.0.iter_mut().zip(&counters.0) {
+            *count += counter.load(Relaxed);
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// Counts by error kind name
+    pub fn to_map(self) -> BTreeMap<String, u64> {
+        ErrorKind::ALL
+            .iter()
+            .map(|kind| {
+                let name = serde_json::to_value(kind).expect("should serialize");
+                let name = name.as_str().expect("should be a string").to_owned();
+                (name, self.0[kind.index()])
+            })
+            .collect()
+    }
+}
+
+/// Operation counters of a single thread
+///
+/// Only ever written by its owning thread, so there is no contention on the hot path;
+/// the metrics thread only reads.
+#[derive(Default)]
+pub struct ThreadStats {
+    pub write_ops: AtomicU64,
+    pub read_ops: AtomicU64,
+    pub delete_ops: AtomicU64,
+    pub scan_ops: AtomicU64,
+
+    /// Accumulated write latency (in microseconds)
+    pub write_latency: AtomicU64,
+
+    /// Accumulated read latency (in microseconds)
+    pub read_latency: AtomicU64,
+
+    pub write_histogram: Histogram,
+    pub read_histogram: Histogram,
+
+    /// Accumulated delay between submitting an operation to the blocking pool
+    /// and it starting to run (in microseconds, tokio driver only)
+    pub scheduling_delay: AtomicU64,
+    pub scheduling_histogram: Histogram,
+
+    pub write_errors: ErrorCounters,
+    pub read_errors: ErrorCounters,
+
+    /// Number of retried operations (with `--on-error retry`)
+    pub retries: AtomicU64,
+}
+
+/// Point-in-time sum of counters
+#[derive(Clone, Copy, Default)]
+pub struct Counters {
+    pub write_ops: u64,
+    pub read_ops: u64,
+    pub delete_ops: u64,
+    pub scan_ops: u64,
+    pub write_latency: u64,
+    pub read_latency: u64,
+    pub scheduling_delay: u64,
+    pub write_errors: ErrorCounts,
+    pub read_errors: ErrorCounts,
+    pub retries: u64,
+}
+
+impl Counters {
+    fn add(&mut self, stats: &ThreadStats) {
+        self.write_ops += stats.write_ops.load(Relaxed);
+        self.read_ops += stats.read_ops.load(Relaxed);
+        self.delete_ops += stats.delete_ops.load(Relaxed);
+        self.scan_ops += stats.scan_ops.load(Relaxed);
+        self.write_latency += stats.write_latency.load(Relaxed);
+        self.read_latency += stats.read_latency.load(Relaxed);
+        self.scheduling_delay += stats.scheduling_delay.load(Relaxed);
+        self.write_errors.add(&stats.write_errors);
+        self.read_errors.add(&stats.read_errors);
+        self.retries += stats.retries.load(Relaxed);
+    }
+
+    pub fn ops(&self) -> u64 {
+        self.write_ops + self.read_ops + self.delete_ops + self.scan_ops
+    }
+}
+
+/// Cache-padded stat slots, one per thread
+///
+/// Slot 0 belongs to the main thread (which loads the data set),
+/// slots `1..=workers` belong to the worker threads.
+///
+/// A slot takes ~0.5 KiB up front; each of its histograms only allocates (~7.6 KiB)
+/// once its thread records into it, so a slot is bounded by ~23 KiB
+/// (a mixed worker with `--driver tokio`) and usually needs one or two histograms.
+/// The metrics thread merges the allocated histograms in place every tick.
+pub struct Stats {
+    slots: Box<[CachePadded<ThreadStats>]>,
+}
+
+impl Stats {
+    pub fn new(workers: usize) -> Self {
+        Self {
+            slots: (0..=workers).map(|_| CachePadded::default()).collect(),
+        }
+    }
+
+    pub fn slot(&self, idx: usize) -> &ThreadStats {
+        &self.slots[idx]
+    }
+
+    /// Counters of each worker thread
+    pub fn workers(&self) -> impl Iterator<Item = Counters> + '_ {
+        self.slots.iter().skip(1).map(|slot| {
+            let mut counters = Counters::default();
+            counters.add(slot);
+            counters
+        })
+    }
+
+    /// Counters summed over all threads
+    pub fn totals(&self) -> Counters {
+        let mut counters = Counters::default();
+
+        for slot in self.slots.iter() {
+            counters.add(slot);
+        }
+
+        counters
+    }
+
+    fn merged(&self, f: impl Fn(&ThreadStats) -> &Histogram) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+
+        for slot in self.slots.iter() {
+            f(slot).merge_into(&mut snapshot);
+        }
+
+        snapshot
+    }
+
+    /// Write latencies merged over all threads
+    pub fn write_histogram(&self) -> Snapshot {
+        self.merged(|stats| &stats.write_histogram)
+    }
+
+    /// Read latencies merged over all threads
+    pub fn read_histogram(&self) -> Snapshot {
+        self.merged(|stats| &stats.read_histogram)
+    }
+
+    /// Scheduling delays merged over all threads
+    pub fn scheduling_histogram(&self) -> Snapshot {
+        self.merged(|stats| &stats.scheduling_histogram)
+    }
+}
@@ -0,0 +1,223 @@
+//! Counts and times sync calls (`fsync`, `fdatasync`, `sync_file_range`, `msync`)
+//!
+//! The worker defines these symbols itself, so the linker resolves every call from Rust code
+//! (including `std`) and statically linked engines (rocksdb, LMDB) to the wrappers below,
+//! which forward to the libc implementation found with `dlsym(RTLD_NEXT)`.
+//! Calls made inside other shared libraries, or raw `syscall`s, are not seen.
+
+use crate::histogram::{Histogram, Snapshot};
+use rust_storage_bench::record::SyncStats;
+use std::{
+    collections::BTreeMap,
+    ffi::{c_int, c_uint, c_void, CStr},
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        LazyLock, OnceLock,
+    },
+    time::Instant,
+};
+
+#[derive(Clone, Copy)]
+enum SyncKind {
+    Fsync,
+    Fdatasync,
+    SyncFileRange,
+    Msync,
+}
+
+impl SyncKind {
+    const ALL: [Self; 4] = [
+        Self::Fsync,
+        Self::Fdatasync,
+        Self::SyncFileRange,
+        Self::Msync,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Fsync => "fsync",
+            Self::Fdatasync => "fdatasync",
+            Self::SyncFileRange => "sync_file_range",
+            Self::Msync => "msync",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Default)]
+struct SyncCounter {
+    calls: AtomicU64,
+    total_micros: AtomicU64,
+
+    /// Latency (in microseconds)
+    latency: Histogram,
+}
+
+static COUNTERS: LazyLock<[SyncCounter; 4]> = LazyLock::new(Default::default);
+
+/// Records a sync call, right after it returned
+///
+/// Keeps `errno`, so callers still see the error of the sync call.
+fn record(kind: SyncKind, start: Instant) {
+    // NOTE: Reading the clock or allocating the histogram may overwrite errno
+    // SAFETY: `__errno_location` returns a valid pointer to the errno of the current thread
+    let errno = unsafe { *libc::__errno_location() };
+
+    let micros = start.elapsed().as_micros() as u64;
+
+    let counter = &COUNTERS[kind.index()];
+    counter.calls.fetch_add(1, Relaxed);
+    counter.total_micros.fetch_add(micros, Relaxed);
+    counter.latency.record(micros);
+
+    // SAFETY: See above
+    unsafe { *libc::__errno_location() = errno };
+}
+
+/// Cumulative sync counts and latencies of all threads
+#[derive(Clone, Default)]
+pub struct SyncSnapshot {
+    calls: [u64; 4],
+    total_micros: [u64; 4],
+    latency: [Snapshot; 4],
+}
+
+pub fn snapshot() -> SyncSnapshot {
+    SyncSnapshot {
+        calls: COUNTERS.each_ref().map(|x| x.calls.load(Relaxed)),
+        total_micros: COUNTERS.each_ref().map(|x| x.total_micros.load(Relaxed)),
+        latency: COUNTERS.each_ref().map(|x| {
+            let mut snapshot = Snapshot::default();
+            x.latency.merge_into(&mut snapshot);
+            snapshot
+        }),
+    }
+}
+
+impl SyncSnapshot {
+    /// Returns the syncs made between `earlier` and `self`
+    pub fn delta(&self, earlier: &Self) -> Self {
+        Self {
+            calls: std::array::from_fn(|i| self.calls[i] - earlier.calls[i]),
+            total_micros: std::array::from_fn(|i| self.total_micros[i] - earlier.total_micros[i]),
+            latency: std::array::from_fn(|i| self.latency[i].delta(&earlier.latency[i])),
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.calls.iter().sum()
+    }
+
+    /// Calls and latency distribution per kind of sync
+    pub fn by_kind(&self) -> BTreeMap<String, SyncStats> {
+        SyncKind::ALL
+            .iter()
+            .map(|kind| {
+                let idx = kind.index();
+                let latency = &self.latency[idx];
+
+                let stats = SyncStats {
+                    calls: self.calls[idx],
+                    total_micros: self.total_micros[idx],
+                    avg_micros: self.total_micros[idx] / self.calls[idx].max(1),
+                    p50_micros: latency.quantile(0.5),
+                    p90_micros: latency.quantile(0.9),
+                    p99_micros: latency.quantile(0.99),
+                    p999_micros: latency.quantile(0.999),
+                    max_micros: latency.max(),
+                };
+
+                (kind.as_str().to_owned(), stats)
+            })
+            .collect()
+    }
+}
+
+/// Looks up the libc implementation of an interposed function
+fn next_symbol(name: &CStr) -> usize {
+    // SAFETY: `name` is a valid C string
+    let ptr = unsafe { libc::dlsym(libc::RTLD_NEXT, name.as_ptr()) };
+    assert!(!ptr.is_null(), "{name:?} not found in any shared library");
+    ptr as usize
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fsync(fd: c_int) -> c_int {
+    static REAL: OnceLock<usize> = OnceLock::new();
+
+    let real: unsafe extern "C" fn(c_int) -> c_int =
+        std::mem::transmute(*REAL.get_or_init(|| next_symbol(c"fsync")));
+
+    let start = Instant::now();
+    let result = real(fd);
+    record(SyncKind::Fsync, start);
+
+    result
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fdatasync(fd: c_int) -> c_int {
+    static REAL: OnceLock<usize> = OnceLock::new();
+
+    let real: unsafe extern "C" fn(c_int) -> c_int =
+        std::mem::transmute(*REAL.get_or_init(|| next_symbol(c"fdatasync")));
+
+    let start = Instant::now();
+    let result = real(fd);
+    record(SyncKind::Fdatasync, start);
+
+    result
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sync_file_range(
+    fd: c_int,
+    offset: libc::off64_t,
+    nbytes: libc::off64_t,
+    flags: c_uint,
+) -> c_int {
+    static REAL: OnceLock<usize> = OnceLock::new();
+
+    let real: unsafe extern "C" fn(c_int, libc::off64_t, libc::off64_t, c_uint) -> c_int =
+        std::mem::transmute(*REAL.get_or_init(|| next_symbol(c"sync_file_range")));
+
+    let start = Instant::now();
+    let result = real(fd, offset, nbytes, flags);
+    record(SyncKind::SyncFileRange, start);
+
+    result
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn msync(addr: *mut c_void, len: libc::size_t, flags: c_int) -> c_int {
+    static REAL: OnceLock<usize> = OnceLock::new();
+
+    let real: unsafe extern "C" fn(*mut c_void, libc::size_t, c_int) -> c_int =
+        std::mem::transmute(*REAL.get_or_init(|| next_symbol(c"msync")));
+
+    let start = Instant::now();
+    let result = real(addr, len, flags);
+    record(SyncKind::Msync, start);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_errno() {
+        // SAFETY: fsync has no preconditions, an invalid descriptor is reported as an error
+        let result = unsafe { fsync(-1) };
+
+        assert_eq!(-1, result);
+        assert_eq!(
+            Some(libc::EBADF),
+            std::io::Error::last_os_error().raw_os_error()
+        );
+    }
+}
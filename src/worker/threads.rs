@@ -0,0 +1,105 @@
+use rust_storage_bench::{
+    procfs::{self, TaskStat},
+    record::{ThreadCpu, ThreadKind, ThreadMetrics, ThreadSample},
+};
+use std::collections::HashMap;
+
+/// Prefix of all threads spawned by the benchmark itself
+pub const THREAD_PREFIX: &str = "bench-";
+
+/// Name of worker threads (followed by the worker index)
+pub const WORKER_THREAD: &str = "bench-worker-";
+
+/// Name of the async runtime's threads, which run the workers when using `--driver tokio`
+pub const TOKIO_THREAD: &str = "bench-tokio";
+
+/// Who a thread's CPU time is attributed to, by its name
+fn kind_of(task: &TaskStat) -> ThreadKind {
+    if task.name.starts_with(WORKER_THREAD) || task.name.starts_with(TOKIO_THREAD) {
+        ThreadKind::Worker
+    } else if task.name.starts_with(THREAD_PREFIX) || task.tid == u64::from(std::process::id()) {
+        ThreadKind::Harness
+    } else {
+        ThreadKind::Engine
+    }
+}
+
+/// Attributes CPU time and context switches to the threads of the process
+pub struct ThreadTracker {
+    prev: HashMap<u64, TaskStat>,
+}
+
+impl ThreadTracker {
+    pub fn new() -> Self {
+        let prev = TaskStat::read_all().unwrap_or_default();
+
+        Self {
+            prev: prev.into_iter().map(|task| (task.tid, task)).collect(),
+        }
+    }
+
+    /// Returns per-thread CPU usage and context switches since the last tick,
+    /// or `None` if `/proc` is not available
+    ///
+    /// Threads that exited since the last tick are not accounted for.
+    pub fn tick(&mut self, elapsed_secs: f64) -> Option<ThreadMetrics> {
+        let tasks = TaskStat::read_all()?;
+
+        let mut cpu_by_kind = HashMap::<ThreadKind, u64>::new();
+        let mut voluntary = 0;
+        let mut involuntary = 0;
+
+        let per_thread = tasks
+            .iter()
+            .map(|task| {
+                let prev = self.prev.get(&task.tid).cloned().unwrap_or_default();
+                let kind = kind_of(task);
+
+                let ticks = (task.utime + task.stime).saturating_sub(prev.utime + prev.stime);
+                *cpu_by_kind.entry(kind).or_default() += ticks;
+
+                // NOTE: A TID may be reused by a new thread with lower counters
+                voluntary += task
+                    .voluntary_ctxt_switches
+                    .saturating_sub(prev.voluntary_ctxt_switches);
+                involuntary += task
+                    .involuntary_ctxt_switches
+                    .saturating_sub(prev.involuntary_ctxt_switches);
+
+                ThreadSample {
+                    tid: task.tid,
+                    name: task.name.clone(),
+                    kind,
+                    cpu: procfs::cpu_percent(ticks, elapsed_secs),
+                    user_secs: procfs::ticks_to_secs(task.utime),
+                    system_secs: procfs::ticks_to_secs(task.stime),
+                    voluntary_ctxt_switches: task.voluntary_ctxt_switches,
+                    involuntary_ctxt_switches: task.involuntary_ctxt_switches,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let cpu = |kind| {
+            procfs::cpu_percent(
+                cpu_by_kind.get(&kind).copied().unwrap_or_default(),
+                elapsed_secs,
+            )
+        };
+
+        let metrics = ThreadMetrics {
+            count: tasks.len(),
+            cpu: ThreadCpu {
+                worker: cpu(ThreadKind::Worker),
+                engine: cpu(ThreadKind::Engine),
+                harness: cpu(ThreadKind::Harness),
+            },
+            voluntary_ctxt_switches: voluntary,
+            involuntary_ctxt_switches: involuntary,
+            per_thread,
+        };
+
+        self.prev = tasks.into_iter().map(|task| (task.tid, task)).collect();
+
+        Some(metrics)
+    }
+}
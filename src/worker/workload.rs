@@ -0,0 +1,446 @@
+use crate::{
+    db::DatabaseWrapper,
+    pacer::{Pacer, TargetRate},
+    recovery::{stamp_sequence_number, AckLog},
+    threads::WORKER_THREAD,
+};
+use rand::{distributions::Distribution, rngs::StdRng, Rng, SeedableRng};
+use rust_storage_bench::{Args, Backend, Driver, LsmCompaction, Role, Workload};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Instant,
+};
+// NOTE: Deprecated in favour of rand_distr::Zipf, which samples different keys for the same seed,
+// so it is kept to stay comparable with earlier results
+#[allow(deprecated)]
+use zipf::ZipfDistribution;
+
+/// Which key a write goes to
+#[derive(Copy, Clone)]
+enum WriteKind {
+    /// Overwrite an existing key (zipfian)
+    Update,
+
+    /// Insert a new key after the latest one
+    Insert,
+}
+
+/// Which key a read goes to
+#[derive(Copy, Clone)]
+enum ReadKind {
+    /// Zipfian over all keys written so far
+    Zipfian,
+
+    /// The latest written key
+    Latest,
+}
+
+/// Operation mix of a workload
+#[derive(Copy, Clone)]
+struct Spec {
+    /// Probability of a write for mixed workers
+    write_ratio: f32,
+
+    write: WriteKind,
+    read: ReadKind,
+}
+
+impl Spec {
+    fn of(workload: Workload) -> Self {
+        use ReadKind::{Latest, Zipfian};
+        use WriteKind::{Insert, Update};
+
+        let (write_ratio, write, read) = match workload {
+            Workload::TaskA => (0.5, Update, Zipfian),
+            Workload::TaskB => (0.05, Update, Zipfian),
+            Workload::TaskC => (0.0, Update, Zipfian),
+            Workload::TaskD => (0.05, Insert, Latest),
+            Workload::TaskE => (0.95, Insert, Latest),
+            Workload::TaskF => (0.05, Insert, Zipfian),
+            Workload::TaskG => (0.95, Insert, Zipfian),
+        };
+
+        Self {
+            write_ratio,
+            write,
+            read,
+        }
+    }
+}
+
+/// A range of keys `0..records`, all sharing the same prefix
+pub struct Keyspace {
+    /// Key prefix, or `None` for plain big-endian integer keys
+    prefix: Option<String>,
+
+    /// Number of keys written (or, with concurrent writers, reserved) so far
+    records: AtomicU64,
+
+    /// Whether more than one thread may write into this key space
+    concurrent_writers: bool,
+
+    /// Whether the storage engine may drop old keys (FIFO compaction)
+    evicting: bool,
+}
+
+impl Keyspace {
+    fn key(&self, x: u64) -> Vec<u8> {
+        match &self.prefix {
+            Some(prefix) => format!("{prefix}:{x:0>10}").into_bytes(),
+            None => x.to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// Worker threads and key spaces of a benchmark run
+pub struct Plan {
+    spec: Spec,
+    keyspaces: Vec<Arc<Keyspace>>,
+
+    /// Role and key space index of each worker
+    workers: Vec<(Role, usize)>,
+
+    acks: Option<Arc<AckLog>>,
+}
+
+impl Plan {
+    /// By default, every worker thread gets its own key space and issues a mix of reads and writes.
+    ///
+    /// With `--readers`/`--writers`, there are dedicated reader and writer threads;
+    /// every writer owns a key space, and the readers are spread over them.
+    ///
+    /// With `--shared-keyspace`, all threads work on the same key space.
+    pub fn new(args: &Args) -> Self {
+        let spec = Spec::of(args.workload);
+
+        let evicting = args.backend == Backend::Fjall && args.lsm_compaction == LsmCompaction::Fifo;
+
+        let dedicated = args.readers.is_some() || args.writers.is_some();
+        let shared = args.shared_keyspace || args.workload == Workload::TaskC;
+
+        let (writers, readers, mixed) = if dedicated {
+            (
+                args.writers.unwrap_or_default(),
+                args.readers.unwrap_or_default(),
+                0,
+            )
+        } else if args.workload == Workload::TaskC && !args.shared_keyspace {
+            // NOTE: Workload C is single-threaded, unless the key space is explicitly shared
+            (0, 0, 1)
+        } else {
+            (0, 0, args.threads)
+        };
+
+        let keyspace_count = if shared {
+            1
+        } else if dedicated {
+            usize::from(writers).max(1)
+        } else {
+            usize::from(mixed).max(1)
+        };
+
+        let workers = std::iter::empty()
+            .chain((0..writers).map(|idx| (Role::Writer, usize::from(idx) % keyspace_count)))
+            .chain((0..readers).map(|idx| (Role::Reader, usize::from(idx) % keyspace_count)))
+            .chain((0..mixed).map(|idx| (Role::Mixed, usize::from(idx) % keyspace_count)))
+            .collect::<Vec<_>>();
+
+        let keyspaces = (0..keyspace_count)
+            .map(|idx| {
+                let prefix = match args.workload {
+                    Workload::TaskC => None,
+                    _ if args.shared_keyspace => Some("shared".to_owned()),
+                    _ => Some(format!("user{idx:0>2}")),
+                };
+
+                let writer_count = workers
+                    .iter()
+                    .filter(|(role, keyspace)| *keyspace == idx && !matches!(role, Role::Reader))
+                    .count();
+
+                Arc::new(Keyspace {
+                    prefix,
+                    records: AtomicU64::default(),
+                    concurrent_writers: writer_count > 1,
+                    evicting,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            spec,
+            keyspaces,
+            workers,
+            acks: None,
+        }
+    }
+
+    /// Logs every write acknowledged as durable
+    pub fn with_ack_log(mut self, acks: AckLog) -> Self {
+        self.acks = Some(Arc::new(acks));
+        self
+    }
+
+    pub fn roles(&self) -> impl Iterator<Item = Role> + '_ {
+        self.workers.iter().map(|(role, _)| *role)
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Loads the initial data set (`--items` keys per key space)
+    pub fn load(&self, db: &DatabaseWrapper, args: &Arc<Args>) {
+        let mut rng = rand::thread_rng();
+
+        for keyspace in &self.keyspaces {
+            for x in 0..args.items.into() {
+                let mut val = random_value(&mut rng, args.value_size);
+
+                if self.acks.is_some() {
+                    // NOTE: Initial values are never acknowledged, so they get the lowest sequence number
+                    stamp_sequence_number(&mut val, 0);
+                }
+
+                // NOTE: The data set needs to be complete, regardless of the error policy
+                db.insert(&keyspace.key(x), &val, false)
+                    .expect("failed to load the initial data set");
+            }
+
+            keyspace.records.store(args.items.into(), Relaxed);
+        }
+    }
+
+    fn workers(
+        &self,
+        db: &DatabaseWrapper,
+        args: &Arc<Args>,
+        rate: &TargetRate,
+    ) -> impl Iterator<Item = Worker> + '_ {
+        let worker_count = self.worker_count() as u32;
+        let db = db.clone();
+        let args = args.clone();
+        let rate = rate.clone();
+
+        self.workers
+            .iter()
+            .enumerate()
+            .map(move |(idx, (role, keyspace))| Worker {
+                db: db.for_worker(idx),
+                args: args.clone(),
+                spec: self.spec,
+                role: *role,
+                keyspace: self.keyspaces[*keyspace].clone(),
+                pacer: Pacer::new(&args, rate.clone(), worker_count),
+                rng: StdRng::from_entropy(),
+                zipf: None,
+                acks: self.acks.clone(),
+            })
+    }
+
+    /// Spawns all workers, using the driver selected by `--driver`
+    pub fn spawn(
+        &self,
+        db: &DatabaseWrapper,
+        args: &Arc<Args>,
+        rate: &TargetRate,
+    ) -> Vec<JoinHandle<()>> {
+        let workers = self.workers(db, args, rate);
+
+        match args.driver {
+            Driver::Threads => workers
+                .enumerate()
+                .map(|(idx, worker)| {
+                    std::thread::Builder::new()
+                        .name(format!("{WORKER_THREAD}{idx}"))
+                        .spawn(move || worker.run())
+                        .expect("failed to spawn worker thread")
+                })
+                .collect(),
+
+            #[cfg(feature = "tokio")]
+            Driver::Tokio => {
+                let workers = workers.collect::<Vec<_>>();
+
+                let runtime = tokio::runtime::Builder::new_multi_thread()
+                    .max_blocking_threads(args.blocking_threads.into())
+                    .enable_time()
+                    .thread_name(crate::threads::TOKIO_THREAD)
+                    .build()
+                    .unwrap();
+
+                let thread =
+                    std::thread::Builder::new().name(crate::threads::TOKIO_THREAD.to_owned());
+
+                vec![thread
+                    .spawn(move || {
+                        runtime.block_on(async move {
+                            let tasks = workers
+                                .into_iter()
+                                .map(|worker| tokio::spawn(worker.run_async()))
+                                .collect::<Vec<_>>();
+
+                            for task in tasks {
+                                task.await.unwrap();
+                            }
+                        });
+                    })
+                    .expect("failed to spawn runtime thread")]
+            }
+        }
+    }
+}
+
+fn random_value<R: Rng>(rng: &mut R, size: u32) -> Vec<u8> {
+    let mut val: Vec<u8> = Vec::with_capacity(size as usize);
+    for _ in 0..size {
+        val.push(rng.gen::<u8>());
+    }
+    val
+}
+
+struct Worker {
+    db: DatabaseWrapper,
+    args: Arc<Args>,
+    spec: Spec,
+    role: Role,
+    keyspace: Arc<Keyspace>,
+    pacer: Pacer,
+    rng: StdRng,
+
+    /// Zipfian distribution, rebuilt whenever the number of keys changes
+    #[allow(deprecated)]
+    zipf: Option<(u64, ZipfDistribution)>,
+
+    /// Log of acknowledged durable writes (crash-recovery child only)
+    acks: Option<Arc<AckLog>>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        loop {
+            let start = self.pacer.wait(&mut self.rng);
+            self.step(start);
+        }
+    }
+
+    /// Issues every operation from a tokio task through `spawn_blocking`,
+    /// recording the delay until the blocking pool picks it up
+    #[cfg(feature = "tokio")]
+    async fn run_async(mut self) {
+        loop {
+            let start = self.pacer.schedule(&mut self.rng);
+
+            // NOTE: Tokio rounds sleeps up to its 1 ms timer resolution,
+            // so only sleep if the operation is not due yet
+            if let Some(start) = start.filter(|start| *start > Instant::now()) {
+                tokio::time::sleep_until(start.into()).await;
+            }
+
+            let submitted = Instant::now();
+
+            self = tokio::task::spawn_blocking(move || {
+                self.db.record_scheduling_delay(submitted.elapsed());
+                self.step(start);
+                self
+            })
+            .await
+            .unwrap();
+        }
+    }
+
+    /// Issues one operation, `start` being its intended start time (`None` in closed-loop mode)
+    fn step(&mut self, start: Option<Instant>) {
+        let write = match self.role {
+            Role::Mixed => self.rng.gen_range(0.0..1.0) < self.spec.write_ratio,
+            Role::Reader => false,
+            Role::Writer => true,
+        };
+
+        if write {
+            self.write(start);
+        } else {
+            self.read(start);
+        }
+    }
+
+    /// Picks an existing key, zipfian over all keys written so far
+    #[allow(deprecated)]
+    fn zipfian(&mut self) -> u64 {
+        let n = self.keyspace.records.load(Relaxed) - 1;
+
+        let zipf = match &self.zipf {
+            Some((prev, zipf)) if *prev == n => zipf,
+            _ => {
+                let zipf = ZipfDistribution::new(n as usize, 0.99).unwrap();
+                &self.zipf.insert((n, zipf)).1
+            }
+        };
+
+        zipf.sample(&mut self.rng) as u64
+    }
+
+    fn write(&mut self, start: Option<Instant>) {
+        let mut val = random_value(&mut self.rng, self.args.value_size);
+
+        let seq = self.acks.as_ref().map(|acks| acks.next_seq());
+
+        if let Some(seq) = seq {
+            stamp_sequence_number(&mut val, seq);
+        }
+
+        let (x, publish) = match self.spec.write {
+            WriteKind::Update => (self.zipfian(), false),
+            WriteKind::Insert if self.keyspace.concurrent_writers => {
+                (self.keyspace.records.fetch_add(1, Relaxed), false)
+            }
+            WriteKind::Insert => (self.keyspace.records.load(Relaxed), true),
+        };
+
+        let key = self.keyspace.key(x);
+
+        // NOTE: In closed-loop mode, only the storage engine call is timed
+        let start = start.unwrap_or_else(Instant::now);
+        let result = self.db.insert_at(start, &key, &val, self.args.fsync);
+
+        // NOTE: The error was counted (or retried) by the error policy
+        if result.is_err() {
+            return;
+        }
+
+        if publish {
+            // NOTE: Only publish the key once it is written,
+            // so readers never look up a key that does not exist yet
+            self.keyspace.records.store(x + 1, Relaxed);
+        }
+
+        if let (Some(acks), Some(seq)) = (&self.acks, seq) {
+            acks.ack(seq, &key);
+        }
+    }
+
+    fn read(&mut self, start: Option<Instant>) {
+        let x = match self.spec.read {
+            ReadKind::Zipfian => self.zipfian(),
+            ReadKind::Latest => self.keyspace.records.load(Relaxed) - 1,
+        };
+
+        let key = self.keyspace.key(x);
+
+        let start = start.unwrap_or_else(Instant::now);
+
+        let Ok(item) = self.db.get_at(start, &key) else {
+            return;
+        };
+
+        // NOTE: With concurrent writers, keys are reserved before they are written,
+        // so a reader may race ahead of a writer
+        if !self.keyspace.concurrent_writers && !self.keyspace.evicting {
+            item.unwrap();
+        }
+    }
+}